@@ -16,6 +16,9 @@ use strum_macros::{EnumIter, EnumString, ToString};
 pub enum ActionType {
     Dir,
     File,
+    SymLink,
+    HardLink,
+    Requirement,
     Zpkg
 }
 
@@ -0,0 +1,53 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::any::Any;
+
+use super::action::Action;
+use crate::action::action::ActionType;
+
+// Links share the inode of target, a File in the same manifest, so carry no ownership or mode
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct HardLink {
+    pub path: String,
+    pub target: String,
+}
+
+impl Action for HardLink {
+    fn id(&self) -> String {
+        format!("{}:{}", self.type_name().to_string(), self.path)
+    }
+
+    fn key(&self) -> String {
+        self.path.clone()
+    }
+
+    fn type_name(&self) -> ActionType {
+        ActionType::HardLink
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.path.is_empty() && !self.target.is_empty()
+    }
+
+    fn to_string(&self) -> String {
+        format!("{} {} => {}", self.type_name().to_string(), self.path, self.target)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl PartialEq for HardLink {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
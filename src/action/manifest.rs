@@ -14,8 +14,14 @@ use std::collections::HashSet;
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone)]
 pub struct Manifest {
     pub zpkg: Zpkg,
+    #[serde(default)]
+    pub requirements: Vec<Requirement>,
     pub dirs: Vec<Dir>,
     pub files: Vec<File>,
+    #[serde(default)]
+    pub symlinks: Vec<SymLink>,
+    #[serde(default)]
+    pub hardlinks: Vec<HardLink>,
 }
 
 // TODO resolve sorting of action vectors
@@ -23,8 +29,11 @@ impl Manifest {
     pub fn new(zpkg: Zpkg) -> Self {
         Self {
             zpkg,
+            requirements: vec![],
             dirs: vec![],
-            files: vec![]
+            files: vec![],
+            symlinks: vec![],
+            hardlinks: vec![]
         }
     }
     
@@ -33,6 +42,10 @@ impl Manifest {
 
         actions.push(Box::new(self.zpkg.clone()));
 
+        for action in self.requirements.iter() {
+            actions.push(Box::new(action.clone()));
+        }
+
         for action in self.dirs.iter() {
             actions.push(Box::new(action.clone()));
         }
@@ -41,6 +54,15 @@ impl Manifest {
             actions.push(Box::new(action.clone()));
         }
 
+        for action in self.symlinks.iter() {
+            actions.push(Box::new(action.clone()));
+        }
+
+        // Links follow the files they point at
+        for action in self.hardlinks.iter() {
+            actions.push(Box::new(action.clone()));
+        }
+
         actions
     }
 
@@ -49,6 +71,11 @@ impl Manifest {
             ActionType::Zpkg => {
                 self.zpkg = action.as_any().downcast_ref::<Zpkg>().unwrap().clone();
             },
+            ActionType::Requirement => {
+                if !self.requirements.contains(action.as_any().downcast_ref::<Requirement>().unwrap()) {
+                    self.requirements.push(action.as_any().downcast_ref::<Requirement>().unwrap().clone());
+                }
+            },
             ActionType::Dir => {
                 if !self.dirs.contains(action.as_any().downcast_ref::<Dir>().unwrap()) {
                     self.dirs.push(action.as_any().downcast_ref::<Dir>().unwrap().clone());
@@ -58,24 +85,49 @@ impl Manifest {
                 if !self.files.contains(action.as_any().downcast_ref::<File>().unwrap()) {
                     self.files.push(action.as_any().downcast_ref::<File>().unwrap().clone());
                 }
+            },
+            ActionType::SymLink => {
+                if !self.symlinks.contains(action.as_any().downcast_ref::<SymLink>().unwrap()) {
+                    self.symlinks.push(action.as_any().downcast_ref::<SymLink>().unwrap().clone());
+                }
+            },
+            ActionType::HardLink => {
+                let link = action.as_any().downcast_ref::<HardLink>().unwrap();
+
+                // A File declared for the same path replaces the link
+                if !self.hardlinks.contains(link) && !self.files.iter().any(|f| f.path == link.path) {
+                    self.hardlinks.push(link.clone());
+                }
             }
         }
     }
 
     pub fn set(&mut self, actions: Vec<Box<dyn Action>>) {
+        self.requirements = Vec::new();
         self.dirs = Vec::new();
         self.files = Vec::new();
+        self.symlinks = Vec::new();
+        self.hardlinks = Vec::new();
 
         for action in actions {
             match action.type_name() {
                 ActionType::Zpkg => {
                   self.zpkg = action.as_any().downcast_ref::<Zpkg>().unwrap().clone();
                 },
+                ActionType::Requirement => {
+                    self.requirements.push(action.as_any().downcast_ref::<Requirement>().unwrap().clone());
+                },
                 ActionType::Dir => {
                     self.dirs.push(action.as_any().downcast_ref::<Dir>().unwrap().clone());
                 },
                 ActionType::File => {
                     self.files.push(action.as_any().downcast_ref::<File>().unwrap().clone());
+                },
+                ActionType::SymLink => {
+                    self.symlinks.push(action.as_any().downcast_ref::<SymLink>().unwrap().clone());
+                },
+                ActionType::HardLink => {
+                    self.hardlinks.push(action.as_any().downcast_ref::<HardLink>().unwrap().clone());
                 }
             }
         }
@@ -89,6 +141,10 @@ impl Manifest {
     }
 
     pub fn validate(&self) -> Result<(), Error> {
+        for action in self.requirements.iter() {
+            crate::Requirement::from_action(action)?;
+        }
+
         // Ensure integrity of FS objects
         let mut index : HashSet<String> = HashSet::new();
 
@@ -106,6 +162,24 @@ impl Manifest {
             index.insert(action.key());
         }
 
+        for action in self.symlinks.iter() {
+            if index.contains(action.key().as_str()) {
+                return Err(anyhow!("duplicate action for key: {}", action.key()))
+            }
+            index.insert(action.key());
+        }
+
+        for action in self.hardlinks.iter() {
+            if index.contains(action.key().as_str()) {
+                return Err(anyhow!("duplicate action for key: {}", action.key()))
+            }
+            index.insert(action.key());
+
+            if !self.files.iter().any(|f| f.path == action.target) {
+                return Err(anyhow!("hardlink {} targets missing file: {}", action.path, action.target))
+            }
+        }
+
         Ok(())
     }
 }
\ No newline at end of file
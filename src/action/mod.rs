@@ -1,6 +1,9 @@
 mod action;
 mod dir;
 mod file;
+mod hardlink;
+mod requirement;
+mod symlink;
 mod zpkg;
 mod manifest;
 
@@ -8,6 +11,9 @@ pub use self::action::Action;
 pub use self::action::ActionType;
 pub use self::dir::Dir;
 pub use self::file::File;
+pub use self::hardlink::HardLink;
+pub use self::requirement::Requirement;
+pub use self::symlink::SymLink;
 pub use self::zpkg::Zpkg;
 pub use self::manifest::Manifest;
 
@@ -0,0 +1,54 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::any::Any;
+
+use super::action::{Action, ActionType};
+
+// Relationship to another package, method is one of depends, provides or conflicts and
+// operation a comparator applied to version, which is empty for ANY
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone)]
+pub struct Requirement {
+    pub name: String,
+    pub method: String,
+    pub operation: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+impl Action for Requirement {
+    fn id(&self) -> String {
+        format!("{}:{}", self.type_name().to_string(), self.key())
+    }
+
+    fn key(&self) -> String {
+        format!("{}:{}:{}:{}", self.method, self.name, self.operation, self.version)
+    }
+
+    fn type_name(&self) -> ActionType {
+        ActionType::Requirement
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.name.is_empty() && !self.method.is_empty() && !self.operation.is_empty()
+    }
+
+    fn to_string(&self) -> String {
+        if self.version.is_empty() {
+            format!("{} {} {} {}", self.type_name().to_string(), self.method, self.name, self.operation)
+        } else {
+            format!("{} {} {} {} {}", self.type_name().to_string(), self.method, self.name, self.operation, self.version)
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
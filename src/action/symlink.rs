@@ -0,0 +1,54 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::any::Any;
+
+use super::action::Action;
+use crate::action::action::ActionType;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct SymLink {
+    pub path: String,
+    pub target: String,
+    pub owner: String,
+    pub group: String,
+}
+
+impl Action for SymLink {
+    fn id(&self) -> String {
+        format!("{}:{}", self.type_name().to_string(), self.path)
+    }
+
+    fn key(&self) -> String {
+        self.path.clone()
+    }
+
+    fn type_name(&self) -> ActionType {
+        ActionType::SymLink
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.path.is_empty() && !self.target.is_empty()
+    }
+
+    fn to_string(&self) -> String {
+        format!("{} {}:{} {} -> {}", self.type_name().to_string(), self.owner, self.group, self.path, self.target)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl PartialEq for SymLink {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
@@ -1,5 +1,8 @@
 use crate::config::Config;
+use crate::db::State;
+use crate::journal::Journal;
 use std::collections::HashMap;
+use anyhow::Error;
 use event_emitter_rs::EventEmitter;
 use serde::Deserialize;
 use crate::Emitter;
@@ -23,6 +26,10 @@ impl ZPS {
         }
     }
 
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn env(&mut self) -> HashMap<String, String> {
         let mut env = HashMap::new();
 
@@ -30,6 +37,17 @@ impl ZPS {
         self.emitter.sync_emit("info", "hey dude".to_string());
         env
     }
+
+    // Rolls back a transaction left behind by a crash or kill
+    pub fn recover(&mut self) -> Result<(), Error> {
+        let mut state = State::new(self.config.db_path().to_str().unwrap());
+
+        if Journal::new(&self.config).recover(&mut state)? {
+            self.emitter.sync_emit("warn", "rolled back an interrupted transaction".to_string());
+        }
+
+        Ok(())
+    }
 }
 
 impl Emitter for ZPS {
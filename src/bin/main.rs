@@ -8,10 +8,22 @@
  * Copyright 2020 Zachary Schneider
  */
 
-use clap::{App, Arg, AppSettings};
+use std::convert::TryFrom;
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use clap::{App, Arg, AppSettings, ArgMatches};
 use zps::app::ZPS;
-use zps::{Emitter, console};
+use zps::config::Config;
 use zps::console::UI;
+use zps::index::{Index, INDEX};
+use zps::publisher::Publisher;
+use zps::zpkg::{Builder, CompType, Extractor, HashMethod, Verifier};
+use zps::zpkg::reader::Reader;
 
 fn main() {
     let matches = App::new("ZPS")
@@ -27,19 +39,459 @@ fn main() {
             .takes_value(true))
         .subcommand(App::new("env")
             .about("dumps ZPS environment"))
+        .subcommand(App::new("publish")
+            .about("publish zpkgs to a repository")
+            .arg(Arg::new("keep")
+                .long("keep")
+                .value_name("COUNT")
+                .about("Versions of each package to keep, older ones are removed")
+                .default_value("10")
+                .takes_value(true))
+            .arg(Arg::new("work")
+                .long("work")
+                .value_name("PATH")
+                .about("Path for temporary files, defaults to the current directory")
+                .takes_value(true))
+            .arg(Arg::new("repo")
+                .value_name("REPO")
+                .about("Repository uri or path")
+                .required(true)
+                .index(1))
+            .arg(Arg::new("path")
+                .value_name("ZPKG")
+                .about("Paths to zpkgs")
+                .required(true)
+                .multiple(true)
+                .index(2)))
+        .subcommand(App::new("repo")
+            .about("repository management")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(App::new("index")
+                .about("generate the repository index for a directory of zpkgs")
+                .arg(Arg::new("output")
+                    .long("output")
+                    .value_name("PATH")
+                    .about("Path to write the index to, defaults to zps.index in DIR")
+                    .takes_value(true))
+                .arg(Arg::new("path")
+                    .value_name("DIR")
+                    .about("Directory containing zpkgs")
+                    .required(true)
+                    .index(1))))
+        .subcommand(App::new("zpkg")
+            .about("zpkg management")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(App::new("build")
+                .about("build a zpkg from a Zpkgfile")
+                .arg(Arg::new("target")
+                    .long("target")
+                    .value_name("PATH")
+                    .about("Path to the proto tree, defaults to proto beside the Zpkgfile")
+                    .takes_value(true))
+                .arg(Arg::new("work")
+                    .long("work")
+                    .value_name("PATH")
+                    .about("Path for temporary build files, defaults to the current directory")
+                    .takes_value(true))
+                .arg(Arg::new("output")
+                    .long("output")
+                    .value_name("PATH")
+                    .about("Output directory, defaults to the current directory")
+                    .takes_value(true))
+                .arg(Arg::new("zpf")
+                    .long("zpf")
+                    .value_name("PATH")
+                    .about("Path to the Zpkgfile or its directory, defaults to ./Zpkgfile")
+                    .takes_value(true))
+                .arg(Arg::new("insecure")
+                    .long("insecure")
+                    .about("Use ownership from the proto tree rather than root"))
+                .arg(Arg::new("restrict")
+                    .long("restrict")
+                    .about("Restrict contents to actions defined in the Zpkgfile"))
+                .arg(Arg::new("owner")
+                    .long("owner")
+                    .value_name("USER")
+                    .about("Override file and directory owner")
+                    .takes_value(true))
+                .arg(Arg::new("group")
+                    .long("group")
+                    .value_name("GROUP")
+                    .about("Override file and directory group")
+                    .takes_value(true))
+                .arg(Arg::new("os")
+                    .long("os")
+                    .value_name("OS")
+                    .about("Target os, defaults to the current os")
+                    .takes_value(true))
+                .arg(Arg::new("arch")
+                    .long("arch")
+                    .value_name("ARCH")
+                    .about("Target arch, defaults to the current arch")
+                    .takes_value(true))
+                .arg(Arg::new("all-platforms")
+                    .long("all-platforms")
+                    .about("Build a zpkg for every platform matching --os and --arch"))
+                .arg(Arg::new("compression")
+                    .long("compression")
+                    .value_name("TYPE")
+                    .about("Payload compression")
+                    .possible_values(&["zstd"])
+                    .default_value("zstd")
+                    .takes_value(true))
+                .arg(Arg::new("debug")
+                    .long("debug")
+                    .about("Enable debug output"))
+                .arg(Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .about("Print each packaged action")))
+            .subcommand(App::new("manifest")
+                .about("print zpkg header and metadata")
+                .arg(Arg::new("json")
+                    .long("json")
+                    .about("Dump the raw manifest as JSON"))
+                .arg(Arg::new("path")
+                    .value_name("ZPKG")
+                    .about("Path to zpkg")
+                    .required(true)
+                    .index(1)))
+            .subcommand(App::new("contents")
+                .about("list zpkg contents")
+                .arg(Arg::new("json")
+                    .long("json")
+                    .about("Dump the raw manifest as JSON"))
+                .arg(Arg::new("path")
+                    .value_name("ZPKG")
+                    .about("Path to zpkg")
+                    .required(true)
+                    .index(1)))
+            .subcommand(App::new("extract")
+                .about("extract zpkg contents into a directory without installing")
+                .arg(Arg::new("permissions")
+                    .long("permissions")
+                    .about("Apply modes from the manifest"))
+                .arg(Arg::new("ownership")
+                    .long("ownership")
+                    .about("Apply owner and group from the manifest, skipped unless root"))
+                .arg(Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .about("Print each extracted action"))
+                .arg(Arg::new("path")
+                    .value_name("ZPKG")
+                    .about("Path to zpkg")
+                    .required(true)
+                    .index(1))
+                .arg(Arg::new("target")
+                    .value_name("DIR")
+                    .about("Directory to extract into")
+                    .required(true)
+                    .index(2)))
+            .subcommand(App::new("verify")
+                .about("verify zpkg payload integrity")
+                .arg(Arg::new("path")
+                    .value_name("ZPKG")
+                    .about("Path to zpkg")
+                    .required(true)
+                    .index(1))))
         .get_matches();
 
     let mut zps = ZPS::new(matches.value_of("tree"));
 
     UI::bind(&mut zps, true);
 
-    match matches.subcommand_name() {
-        Some("env") => {
+    if let Err(err) = zps.recover() {
+        UI::error(&err, true);
+        process::exit(1);
+    }
+
+    let result = match matches.subcommand() {
+        Some(("env", _)) => {
             for (k, v) in zps.env() {
                 println!("{}: {}", k, v)
             }
+            Ok(())
+        },
+        Some(("publish", publish_matches)) => publish(zps.config(), publish_matches),
+        Some(("repo", repo_matches)) => match repo_matches.subcommand() {
+            Some(("index", index_matches)) => repo_index(index_matches),
+            _ => Ok(()),
         },
-        None => (),
-        _ => println!("Command not found"),
+        Some(("zpkg", zpkg_matches)) => match zpkg_matches.subcommand() {
+            Some(("build", build_matches)) => zpkg_build(build_matches),
+            Some(("manifest", manifest_matches)) => zpkg_manifest(manifest_matches),
+            Some(("contents", contents_matches)) => zpkg_contents(contents_matches),
+            Some(("extract", extract_matches)) => zpkg_extract(extract_matches),
+            Some(("verify", verify_matches)) => zpkg_verify(verify_matches),
+            _ => Ok(()),
+        },
+        None => Ok(()),
+        _ => {
+            println!("Command not found");
+            Ok(())
+        },
+    };
+
+    if let Err(err) = result {
+        UI::error(&err, true);
+        process::exit(1);
+    }
+}
+
+fn publish(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let mut publisher = Publisher::new(config);
+
+    publisher
+        .repo(matches.value_of("repo").unwrap().to_string())
+        .retention(matches.value_of("keep").unwrap().parse().map_err(|_| anyhow!("invalid count for --keep"))?);
+
+    if let Some(work) = matches.value_of("work") {
+        publisher.work(work.to_string());
+    }
+
+    for path in matches.values_of("path").unwrap() {
+        publisher.zpkg(path.to_string());
+    }
+
+    let report = publisher.publish()?;
+
+    for file_name in report.published.iter() {
+        println!("{:<10}{}", "PUBLISHED", file_name);
+    }
+
+    for file_name in report.rejected.iter() {
+        println!("{:<10}{}: already in repository", "REJECTED", file_name);
+    }
+
+    for file_name in report.pruned.iter() {
+        println!("{:<10}{}", "PRUNED", file_name);
+    }
+
+    Ok(())
+}
+
+fn repo_index(matches: &ArgMatches) -> Result<(), Error> {
+    let dir = Path::new(matches.value_of("path").unwrap());
+
+    let output = match matches.value_of("output") {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => dir.join(INDEX),
+    };
+
+    let index = Index::generate(dir, env::current_dir()?.as_path())?;
+    index.write(&output)?;
+
+    for entry in index.packages.iter() {
+        println!("{}", entry.file_name);
+    }
+
+    Ok(())
+}
+
+fn zpkg_build(matches: &ArgMatches) -> Result<(), Error> {
+    let mut builder = Builder::new();
+
+    UI::bind(&mut builder, true);
+
+    builder.compression(CompType::from_str(matches.value_of("compression").unwrap())?);
+
+    if let Some(target) = matches.value_of("target") {
+        builder.target(target.to_string());
+    }
+
+    if let Some(work) = matches.value_of("work") {
+        builder.work(work.to_string());
+    }
+
+    if let Some(output) = matches.value_of("output") {
+        builder.output(output.to_string());
+    }
+
+    if let Some(zpf) = matches.value_of("zpf") {
+        builder.zpf(zpf.to_string());
+    }
+
+    if let Some(owner) = matches.value_of("owner") {
+        builder.owner(owner.to_string());
+    }
+
+    if let Some(group) = matches.value_of("group") {
+        builder.group(group.to_string());
     }
+
+    if let Some(os) = matches.value_of("os") {
+        builder.os(os.to_string());
+    }
+
+    if let Some(arch) = matches.value_of("arch") {
+        builder.arch(arch.to_string());
+    }
+
+    if matches.is_present("all-platforms") {
+        builder.all_platforms();
+    }
+
+    if matches.is_present("insecure") {
+        builder.insecure();
+    }
+
+    if matches.is_present("restrict") {
+        builder.restrict();
+    }
+
+    if matches.is_present("debug") {
+        builder.debug();
+    }
+
+    if matches.is_present("verbose") {
+        builder.verbose();
+    }
+
+    builder.build()?;
+
+    for path in builder.file_paths() {
+        println!("{}", path.file_name().unwrap().to_string_lossy());
+    }
+
+    Ok(())
+}
+
+fn zpkg_read(matches: &ArgMatches) -> Result<Reader, Error> {
+    let mut reader = Reader::new(Path::new(matches.value_of("path").unwrap()), env::current_dir()?.as_path());
+
+    reader.read()?;
+
+    Ok(reader)
+}
+
+fn zpkg_json(reader: &Reader) -> Result<(), Error> {
+    let mut stdout = std::io::stdout();
+
+    stdout.write_all(&reader.manifest.as_ref().unwrap().to_json()?)?;
+    stdout.write_all(b"\n")?;
+
+    Ok(())
+}
+
+fn zpkg_manifest(matches: &ArgMatches) -> Result<(), Error> {
+    let reader = zpkg_read(matches)?;
+
+    if matches.is_present("json") {
+        return zpkg_json(&reader);
+    }
+
+    let header = reader.header.as_ref().unwrap();
+    let zpkg = &reader.manifest.as_ref().unwrap().zpkg;
+
+    println!("Header:");
+    println!("  {:<13}{}", "version:", header.version());
+    println!("  {:<13}{}", "compression:", CompType::try_from(header.comp_type())?);
+    println!("  {:<13}{}", "hash method:", HashMethod::try_from(header.hash_method())?);
+    println!("  {:<13}{} bytes", "manifest:", header.manifest_len());
+    println!("Zpkg:");
+    println!("  {:<13}{}", "name:", zpkg.name);
+    println!("  {:<13}{}", "version:", zpkg.version);
+    println!("  {:<13}{}", "publisher:", zpkg.publisher);
+    println!("  {:<13}{}", "os:", zpkg.os);
+    println!("  {:<13}{}", "arch:", zpkg.arch);
+    println!("  {:<13}{}", "summary:", zpkg.summary);
+    println!("  {:<13}{}", "description:", zpkg.description);
+
+    let requirements = &reader.manifest.as_ref().unwrap().requirements;
+
+    if !requirements.is_empty() {
+        println!("Requirements:");
+    }
+
+    for requirement in requirements.iter() {
+        match requirement.version.is_empty() {
+            true => println!("  {:<13}{}", format!("{}:", requirement.method), requirement.name),
+            false => println!("  {:<13}{} {} {}", format!("{}:", requirement.method), requirement.name, requirement.operation, requirement.version),
+        }
+    }
+
+    Ok(())
+}
+
+fn zpkg_contents(matches: &ArgMatches) -> Result<(), Error> {
+    let reader = zpkg_read(matches)?;
+
+    if matches.is_present("json") {
+        return zpkg_json(&reader);
+    }
+
+    let manifest = reader.manifest.as_ref().unwrap();
+
+    for dir in manifest.dirs.iter() {
+        println!("{:<8} {:04o} {:<20} {:>10} {:>10} {:<64} {}",
+                 "Dir", dir.mode, format!("{}:{}", dir.owner, dir.group), "-", "-", "-", dir.path);
+    }
+
+    for file in manifest.files.iter() {
+        let digest = if file.digest.is_empty() { "-" } else { file.digest.as_str() };
+
+        println!("{:<8} {:04o} {:<20} {:>10} {:>10} {:<64} {}",
+                 "File", file.mode, format!("{}:{}", file.owner, file.group), file.size, file.csize, digest, file.path);
+    }
+
+    for symlink in manifest.symlinks.iter() {
+        println!("{:<8} {:>4} {:<20} {:>10} {:>10} {:<64} {} -> {}",
+                 "SymLink", "-", format!("{}:{}", symlink.owner, symlink.group), "-", "-", "-", symlink.path, symlink.target);
+    }
+
+    for hardlink in manifest.hardlinks.iter() {
+        println!("{:<8} {:>4} {:<20} {:>10} {:>10} {:<64} {} => {}",
+                 "HardLink", "-", "-", "-", "-", "-", hardlink.path, hardlink.target);
+    }
+
+    Ok(())
+}
+
+fn zpkg_extract(matches: &ArgMatches) -> Result<(), Error> {
+    let mut extractor = Extractor::new();
+
+    UI::bind(&mut extractor, true);
+
+    extractor
+        .zpkg(matches.value_of("path").unwrap().to_string())
+        .target(matches.value_of("target").unwrap().to_string());
+
+    if matches.is_present("permissions") {
+        extractor.permissions();
+    }
+
+    if matches.is_present("ownership") {
+        extractor.ownership();
+    }
+
+    if matches.is_present("verbose") {
+        extractor.verbose();
+    }
+
+    extractor.extract()?;
+
+    Ok(())
+}
+
+fn zpkg_verify(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches.value_of("path").unwrap();
+    let report = Verifier::new(Path::new(path), env::current_dir()?.as_path()).verify()?;
+
+    for file in report.files.iter() {
+        match file.error.as_ref() {
+            None => println!("{:<7}{}", "OK", file.path),
+            Some(err) => println!("{:<7}{}: {}", "FAILED", file.path, err),
+        }
+    }
+
+    for err in report.errors.iter() {
+        println!("{:<7}{}", "FAILED", err);
+    }
+
+    if !report.is_ok() {
+        return Err(anyhow!("verification failed for {}", path));
+    }
+
+    Ok(())
 }
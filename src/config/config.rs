@@ -1,7 +1,10 @@
 use std::path::{Path, PathBuf};
+use anyhow::Error;
 use crate::platform::{OSArch, OS, Arch};
 use crate::config::path::*;
+use crate::config::s3::{S3Config, S3_CONFIG};
 
+#[derive(Clone)]
 pub struct Config {
     os_arch: OSArch,
     tree: PathBuf
@@ -35,7 +38,7 @@ impl Config {
     }
 
     pub fn cache_path(&self) -> PathBuf {
-        Path::join(self.tree().as_path(), TMP)
+        Path::join(self.tree().as_path(), CACHE)
     }
 
     pub fn config_path(&self) -> PathBuf {
@@ -46,9 +49,17 @@ impl Config {
         Path::join(self.tree().as_path(), DATA)
     }
 
+    pub fn db_path(&self) -> PathBuf {
+        Path::join(self.tree().as_path(), DB)
+    }
+
     pub fn tmp_path(&self) -> PathBuf {
         Path::join(self.tree().as_path(), TMP)
     }
+
+    pub fn s3(&self) -> Result<S3Config, Error> {
+        S3Config::load(&self.config_path().join(S3_CONFIG))
+    }
 }
 
 fn osarch_from_config(tree: &Path) -> OSArch {
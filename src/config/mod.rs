@@ -1,4 +1,6 @@
 mod config;
 mod path;
+mod s3;
 
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+pub use s3::*;
\ No newline at end of file
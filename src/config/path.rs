@@ -3,6 +3,7 @@ use std::path::PathBuf;
 pub(crate) const BIN_POSTFIX: &str = "usr/bin/zps";
 pub(crate) const CACHE: &str = "var/cache/zps";
 pub(crate) const DATA: &str = "var/lib/zps";
+pub(crate) const DB: &str = "var/lib/zps/db";
 pub(crate) const ETC: &str = "etc/zps";
 pub(crate) const TMP: &str = "var/tmp/zps";
 pub(crate) const TREE: &str = "zps";
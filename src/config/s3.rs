@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Error};
+
+pub(crate) const S3_CONFIG: &str = "s3.json";
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+// Settings for s3 repositories, requests are anonymous without credentials
+#[derive(serde::Deserialize, Clone)]
+pub struct S3Config {
+    // Any S3 compatible store, AWS when not set
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default = "default_region")]
+    pub region: String,
+    #[serde(default)]
+    pub access_key: Option<String>,
+    #[serde(default)]
+    pub secret_key: Option<String>,
+}
+
+impl S3Config {
+    pub fn load(path: &Path) -> Result<S3Config, Error> {
+        if !path.exists() {
+            return Ok(S3Config::default());
+        }
+
+        serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|err| anyhow!("invalid s3 config {}: {}", path.display(), err))
+    }
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        S3Config {
+            endpoint: None,
+            region: default_region(),
+            access_key: None,
+            secret_key: None,
+        }
+    }
+}
+
+fn default_region() -> String {
+    DEFAULT_REGION.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_load() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstests3config");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root)?;
+
+        let config = S3Config::load(&root.join(S3_CONFIG))?;
+        assert_eq!(config.region, "us-east-1");
+        assert!(config.endpoint.is_none());
+
+        std::fs::write(root.join(S3_CONFIG), r#"{"endpoint": "http://localhost:9000", "access_key": "zps"}"#)?;
+        let config = S3Config::load(&root.join(S3_CONFIG))?;
+        assert_eq!(config.endpoint.unwrap(), "http://localhost:9000");
+        assert_eq!(config.region, "us-east-1");
+        assert_eq!(config.access_key.unwrap(), "zps");
+
+        std::fs::write(root.join(S3_CONFIG), "endpoint")?;
+        assert!(S3Config::load(&root.join(S3_CONFIG)).err().unwrap().to_string().starts_with("invalid s3 config"));
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
+use anyhow::Error;
+
 use crate::Emitter;
+use crate::solver::Unsatisfiable;
 
 pub struct UI {}
 
@@ -7,6 +10,10 @@ impl UI {
         emitter.on("info", move |msg: String| {
             Self::info(msg, color)
         });
+
+        emitter.on("warn", move |msg: String| {
+            Self::warn(msg, color)
+        });
     }
 
     fn info(msg: String, color: bool) {
@@ -16,4 +23,33 @@ impl UI {
             println!("{}", msg)
         }
     }
-}
\ No newline at end of file
+
+    fn warn(msg: String, color: bool) {
+        if color {
+            eprintln!("Warning: {}", msg)
+        } else {
+            eprintln!("Warning: {}", msg)
+        }
+    }
+
+    pub fn error(err: &Error, color: bool) {
+        for line in Self::error_lines(err) {
+            if color {
+                eprintln!("{}", line)
+            } else {
+                eprintln!("{}", line)
+            }
+        }
+    }
+
+    // Unsatisfiable requests get the full derivation rather than just its summary
+    pub(crate) fn error_lines(err: &Error) -> Vec<String> {
+        let mut lines = vec![format!("Error: {}", err)];
+
+        if let Some(unsatisfiable) = err.downcast_ref::<Unsatisfiable>() {
+            lines.extend(unsatisfiable.derivation.lines().iter().map(|line| format!("  {}", line)));
+        }
+
+        lines
+    }
+}
@@ -11,7 +11,7 @@
 use super::action::*;
 use kv::*;
 
-struct State {
+pub(crate) struct State {
     path: String,
     store: Option<Store>,
 }
@@ -53,6 +53,12 @@ impl State {
         Ok(())
     }
 
+    pub fn pkg_get(&mut self, pkg: String) -> Result<Option<Manifest>, Error> {
+        let packages = self.packages()?;
+
+        Ok(packages.get(pkg)?.map(|p| p.into_inner()))
+    }
+
     pub fn pkg_del(&mut self, pkg: String) -> Result<(), Error> {
         let packages = self.packages()?;
 
@@ -109,6 +115,27 @@ mod tests {
         state.pkg_del( "test".to_string())
     }
 
+    #[test]
+    fn test_get_pkg() -> Result<(), Error> {
+        let mut state = State::new("/tmp/zpstestget");
+
+        state.pkg_put(Manifest::new( Zpkg {
+            name: "test".to_string(),
+            version: "1.0.0:20200320T221640Z".to_string(),
+            publisher: "fezz.io".to_string(),
+            arch: Arch::X8664.to_string(),
+            os: OS::Darwin.to_string(),
+            summary: "Test zpkg".to_string(),
+            description: "Test zpkg, for well testing".to_string()
+        }))?;
+        assert_eq!(state.pkg_get("test".to_string())?.unwrap().zpkg.version, "1.0.0:20200320T221640Z");
+
+        state.pkg_del("test".to_string())?;
+        assert!(state.pkg_get("test".to_string())?.is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_list_pkg() -> Result<(), Error> {
         let mut state = State::new("/tmp/zpstestlist");
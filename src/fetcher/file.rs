@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+use url::Url;
+
+use crate::fetcher::{Fetcher, NotFound, Publisher};
+
+// Repositories on a local or network mounted filesystem
+pub struct FileFetcher {
+    path: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new(uri: &Url) -> Result<FileFetcher, Error> {
+        let path = uri.to_file_path().map_err(|_| anyhow!("invalid file repository: {}", uri))?;
+
+        Ok(FileFetcher { path })
+    }
+}
+
+impl Fetcher for FileFetcher {
+    fn fetch(&self, name: &str, dest: &Path) -> Result<(), Error> {
+        let src = self.path.join(name);
+
+        if !src.is_file() {
+            return Err(NotFound(format!("{} not found in {}", name, self.path.display())).into());
+        }
+
+        std::fs::copy(&src, dest)?;
+
+        Ok(())
+    }
+}
+
+impl Publisher for FileFetcher {
+    fn publish(&self, src: &Path, name: &str) -> Result<(), Error> {
+        std::fs::create_dir_all(&self.path)?;
+
+        let tmp_path = self.path.join(format!(".{}.zpstmp", name));
+
+        if let Err(err) = std::fs::copy(src, &tmp_path) {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(anyhow!("failed to publish {} to {}: {}", name, self.path.display(), err));
+        }
+
+        std::fs::rename(&tmp_path, self.path.join(name))?;
+
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<(), Error> {
+        match std::fs::remove_file(self.path.join(name)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_fetch() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestfetchfile");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("repo"))?;
+        std::fs::write(root.join("repo/nachos"), "nachos")?;
+
+        let fetcher = FileFetcher::new(&Url::from_file_path(root.join("repo")).unwrap())?;
+
+        fetcher.fetch("nachos", &root.join("fetched"))?;
+        assert_eq!(std::fs::read(root.join("fetched"))?, b"nachos");
+
+        let err = fetcher.fetch("salsa", &root.join("salsa")).err().unwrap();
+        assert_eq!(err.to_string(), format!("salsa not found in {}", root.join("repo").display()));
+        assert!(err.is::<NotFound>());
+        assert!(!root.join("salsa").exists());
+
+        assert!(FileFetcher::new(&Url::parse("file://remote.host/repo").unwrap()).is_err());
+
+        let publisher = FileFetcher::new(&Url::from_file_path(root.join("published")).unwrap())?;
+        publisher.publish(&root.join("fetched"), "nachos")?;
+        publisher.publish(&root.join("fetched"), "salsa")?;
+        assert_eq!(std::fs::read(root.join("published/salsa"))?, b"nachos");
+
+        publisher.remove("salsa")?;
+        publisher.remove("salsa")?;
+        assert!(!root.join("published/salsa").exists());
+
+        let mut published: Vec<_> = std::fs::read_dir(root.join("published"))?
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        published.sort();
+        assert_eq!(published, vec!["nachos"]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Error};
+use url::Url;
+
+use crate::fetcher::{Fetcher, NotFound, Validators};
+
+const TIMEOUT_CONNECT: u64 = 10_000;
+const TIMEOUT_READ: u64 = 60_000;
+
+// Repositories served over http or https
+pub struct HttpFetcher {
+    base: Url,
+}
+
+impl HttpFetcher {
+    pub fn new(uri: &Url) -> Result<HttpFetcher, Error> {
+        let mut base = uri.clone();
+
+        // Names are joined onto the base, which only works for a directory
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+
+        Ok(HttpFetcher { base })
+    }
+
+    fn request(&self, name: &str) -> Result<ureq::Request, Error> {
+        Ok(request("GET", &self.base.join(name)?))
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    fn fetch(&self, name: &str, dest: &Path) -> Result<(), Error> {
+        get(self.request(name)?, dest)
+    }
+
+    fn fetch_if_modified(&self, name: &str, dest: &Path, validators: &mut Validators) -> Result<bool, Error> {
+        get_if_modified(self.request(name)?, dest, validators)
+    }
+}
+
+// The pieces below are shared with other fetchers speaking http
+
+pub(crate) fn request(method: &str, url: &Url) -> ureq::Request {
+    let mut request = ureq::request(method, url.as_str());
+    request.timeout_connect(TIMEOUT_CONNECT).timeout_read(TIMEOUT_READ);
+
+    request
+}
+
+pub(crate) fn call(request: &mut ureq::Request, payload: Option<&mut dyn Read>) -> Result<ureq::Response, Error> {
+    let response = match payload {
+        Some(payload) => request.send(payload),
+        None => request.call(),
+    };
+
+    if let Some(err) = response.synthetic_error() {
+        return Err(anyhow!("{}: {}", request.get_url(), err));
+    }
+
+    Ok(response)
+}
+
+pub(crate) fn status(response: &ureq::Response) -> Error {
+    let message = format!("{}: {} {}", response.get_url(), response.status(), response.status_text());
+
+    match response.status() {
+        404 => NotFound(message).into(),
+        _ => anyhow!(message),
+    }
+}
+
+// Partial content at dest is resumed with a range request
+pub(crate) fn get(mut request: ureq::Request, dest: &Path) -> Result<(), Error> {
+    let offset = std::fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+
+    if offset > 0 {
+        request.set("Range", &format!("bytes={}-", offset));
+    }
+
+    let response = call(&mut request, None)?;
+
+    match response.status() {
+        200 => write(response, dest, false),
+        206 => write(response, dest, true),
+        // Nothing left past the offset, let the caller decide whether what's there is valid
+        416 if offset > 0 => Ok(()),
+        _ => Err(status(&response)),
+    }
+}
+
+pub(crate) fn get_if_modified(mut request: ureq::Request, dest: &Path, validators: &mut Validators) -> Result<bool, Error> {
+    if let Some(etag) = validators.etag.as_ref() {
+        request.set("If-None-Match", etag);
+    }
+
+    if let Some(last_modified) = validators.last_modified.as_ref() {
+        request.set("If-Modified-Since", last_modified);
+    }
+
+    let response = call(&mut request, None)?;
+
+    match response.status() {
+        304 => Ok(false),
+        200 => {
+            validators.etag = response.header("ETag").map(|v| v.to_string());
+            validators.last_modified = response.header("Last-Modified").map(|v| v.to_string());
+
+            write(response, dest, false)?;
+
+            Ok(true)
+        },
+        _ => Err(status(&response)),
+    }
+}
+
+fn write(response: ureq::Response, dest: &Path, append: bool) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(dest)?;
+
+    io::copy(&mut response.into_reader(), &mut file)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Repo;
+    use crate::config::Config;
+    use crate::fetcher::Cache;
+    use crate::index::{Index, INDEX};
+    use crate::zpkg::tests::{build_zpkg, test_root};
+    use std::env;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use tiny_http::{Header, Response, Server};
+
+    // Serves files from root with an ETag, conditional and range support, recording each request
+    fn serve(root: PathBuf) -> (Url, Arc<Mutex<Vec<String>>>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/repo", server.server_addr())).unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let requests = log.clone();
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let header = |name: &str| request.headers().iter()
+                    .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+                    .map(|h| h.value.as_str().to_string());
+
+                let if_none_match = header("If-None-Match");
+                let range = header("Range");
+
+                let path = root.join(request.url().trim_start_matches("/repo/"));
+                let data = match std::fs::read(&path) {
+                    Ok(data) => data,
+                    Err(_) => {
+                        log.lock().unwrap().push(format!("{} 404", request.url()));
+                        let _ = request.respond(Response::empty(404));
+                        continue;
+                    },
+                };
+
+                let etag = format!("\"{}\"", crate::fs::digest(&path).unwrap());
+                let etag_header = Header::from_bytes("ETag", etag.as_str()).unwrap();
+
+                if if_none_match.as_ref() == Some(&etag) {
+                    log.lock().unwrap().push(format!("{} 304", request.url()));
+                    let _ = request.respond(Response::empty(304).with_header(etag_header));
+                    continue;
+                }
+
+                if let Some(range) = range {
+                    let offset: usize = range.trim_start_matches("bytes=").trim_end_matches('-').parse().unwrap();
+                    log.lock().unwrap().push(format!("{} 206 {}", request.url(), range));
+
+                    let _ = request.respond(Response::from_data(data[offset..].to_vec()).with_status_code(206));
+                    continue;
+                }
+
+                log.lock().unwrap().push(format!("{} 200", request.url()));
+                let _ = request.respond(Response::from_data(data).with_header(etag_header));
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[test]
+    fn test_fetch() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestfetchhttp");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("repo"))?;
+        std::fs::write(root.join("repo/nachos"), "nachos and salsa")?;
+
+        let (url, requests) = serve(root.join("repo"));
+        let fetcher = HttpFetcher::new(&url)?;
+
+        fetcher.fetch("nachos", &root.join("fetched"))?;
+        assert_eq!(std::fs::read(root.join("fetched"))?, b"nachos and salsa");
+
+        std::fs::write(root.join("partial"), "nachos")?;
+        fetcher.fetch("nachos", &root.join("partial"))?;
+        assert_eq!(std::fs::read(root.join("partial"))?, b"nachos and salsa");
+
+        let mut validators = Validators::default();
+        assert!(fetcher.fetch_if_modified("nachos", &root.join("conditional"), &mut validators)?);
+        assert!(validators.etag.is_some());
+        assert!(!fetcher.fetch_if_modified("nachos", &root.join("conditional"), &mut validators)?);
+
+        let err = fetcher.fetch("salsa", &root.join("salsa")).err().unwrap();
+        assert_eq!(err.to_string(), format!("{}/salsa: 404 Not Found", url));
+        assert!(err.is::<NotFound>());
+
+        assert_eq!(*requests.lock().unwrap(), vec![
+            "/repo/nachos 200",
+            "/repo/nachos 206 bytes=6-",
+            "/repo/nachos 200",
+            "/repo/nachos 304",
+            "/repo/salsa 404",
+        ]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache() -> Result<(), Error> {
+        let root = test_root("zpstestfetchhttpcache")?;
+        build_zpkg(&root, "nachos", "1.0.0", &root.join("repo"))?;
+
+        Index::generate(&root.join("repo"), &root)?.write(&root.join("repo").join(INDEX))?;
+
+        let (url, requests) = serve(root.join("repo"));
+        let config = Config::for_tree(&root.join("tree"));
+        let cache = Cache::new(&config);
+        let mut repo = Repo::new(url, 10, true);
+
+        cache.refresh(&mut repo)?;
+        cache.refresh(&mut repo)?;
+        assert_eq!(repo.clone().contents().len(), 1);
+
+        // An interrupted download picks up where it left off
+        let package = repo.clone().contents().remove(0);
+        let zpkg = std::fs::read(root.join("repo").join(package.file_name()))?;
+        std::fs::write(config.cache_path().join(format!(".{}.zpstmp", package.file_name())), &zpkg[..100])?;
+
+        let path = cache.fetch(&repo, &package)?;
+        assert_eq!(std::fs::read(&path)?, zpkg);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0], "/repo/zps.index 200");
+        assert_eq!(requests[1], "/repo/zps.index 304");
+        assert!(requests[2].ends_with("206 bytes=100-"), "{}", requests[2]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
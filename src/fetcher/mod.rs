@@ -0,0 +1,290 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+mod file;
+mod http;
+mod s3;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+use sha3::{Digest, Sha3_256};
+use url::Url;
+
+use crate::{Package, Repo};
+use crate::config::Config;
+use crate::index::{Index, INDEX};
+use file::*;
+use http::*;
+use s3::*;
+
+const INDEX_DIR: &str = "index";
+
+// Cache validators from the last successful fetch of a file
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+// Fetch failures where the repository has no such file, as opposed to being unreachable
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+pub trait Fetcher {
+    // Retrieves name, relative to the repository root, into dest. Fetchers able to
+    // resume treat an existing dest as the start of the file.
+    fn fetch(&self, name: &str, dest: &Path) -> Result<(), Error>;
+
+    // As fetch but skipped when validators show the copy at dest is current, returns whether
+    // dest was written. Validators are updated for the next call.
+    fn fetch_if_modified(&self, name: &str, dest: &Path, validators: &mut Validators) -> Result<bool, Error> {
+        let _ = std::fs::remove_file(dest);
+        self.fetch(name, dest)?;
+
+        *validators = Validators::default();
+
+        Ok(true)
+    }
+}
+
+pub trait Publisher {
+    // Stores src in the repository as name, readers see either the previous file or
+    // all of the new one
+    fn publish(&self, src: &Path, name: &str) -> Result<(), Error>;
+
+    // Removing a name which isn't there is not an error
+    fn remove(&self, name: &str) -> Result<(), Error>;
+}
+
+pub fn fetcher_for(uri: &Url, config: &Config) -> Result<Box<dyn Fetcher>, Error> {
+    match uri.scheme() {
+        "file" => Ok(Box::new(FileFetcher::new(uri)?)),
+        "http" | "https" => Ok(Box::new(HttpFetcher::new(uri)?)),
+        "s3" => Ok(Box::new(S3Fetcher::new(uri, &config.s3()?)?)),
+        scheme => Err(anyhow!("unsupported repository scheme: {}", scheme)),
+    }
+}
+
+pub fn publisher_for(uri: &Url, config: &Config) -> Result<Box<dyn Publisher>, Error> {
+    match uri.scheme() {
+        "file" => Ok(Box::new(FileFetcher::new(uri)?)),
+        "s3" => Ok(Box::new(S3Fetcher::new(uri, &config.s3()?)?)),
+        scheme => Err(anyhow!("publishing is not supported for scheme: {}", scheme)),
+    }
+}
+
+// Local copies of repository indexes and the packages fetched from them
+pub(crate) struct Cache {
+    path: PathBuf,
+    config: Config,
+}
+
+impl Cache {
+    pub fn new(config: &Config) -> Cache {
+        Cache {
+            path: config.cache_path(),
+            config: config.clone(),
+        }
+    }
+
+    // Indexes are kept per repository, named for a digest of the uri
+    fn index_path(&self, repo: &Repo) -> PathBuf {
+        let mut hasher = Sha3_256::new();
+        hasher.update(repo.uri.as_str().as_bytes());
+
+        self.path.join(INDEX_DIR).join(format!("{:x}", hasher.finalize()))
+    }
+
+    // Fetches the current index and replaces the repo contents with it, the cached
+    // index is only replaced once the new one has been read successfully
+    pub fn refresh(&self, repo: &mut Repo) -> Result<(), Error> {
+        let path = self.index_path(repo);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+
+        let tmp_path = path.with_extension("zpstmp");
+        let validators_path = path.with_extension("validators");
+
+        // Validators are only good for as long as the index they describe is around
+        let mut validators: Validators = match path.exists() {
+            true => std::fs::read(&validators_path).ok()
+                .and_then(|v| serde_json::from_slice(&v).ok())
+                .unwrap_or_default(),
+            false => Validators::default(),
+        };
+
+        let _ = std::fs::remove_file(&tmp_path);
+
+        let fetched = fetcher_for(&repo.uri, &self.config)?.fetch_if_modified(INDEX, &tmp_path, &mut validators)
+            .and_then(|modified| match modified {
+                true => Index::read(&tmp_path).map(Some),
+                false => Ok(None),
+            })
+            .map_err(|err| {
+                let _ = std::fs::remove_file(&tmp_path);
+                anyhow!("failed to refresh {}: {}", repo.uri, err)
+            })?;
+
+        let index = match fetched {
+            Some(index) => {
+                std::fs::rename(&tmp_path, &path)?;
+                std::fs::write(&validators_path, serde_json::to_vec(&validators)?)?;
+                index
+            },
+            None => Index::read(&path)?,
+        };
+
+        self.apply(repo, &index)
+    }
+
+    // Loads the previously refreshed index, if any, without touching the repository
+    pub fn load(&self, repo: &mut Repo) -> Result<bool, Error> {
+        let path = self.index_path(repo);
+
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        self.apply(repo, &Index::read(&path)?)?;
+
+        Ok(true)
+    }
+
+    fn apply(&self, repo: &mut Repo, index: &Index) -> Result<(), Error> {
+        let packages = index.packages()?;
+
+        repo.packages = HashSet::new();
+        repo.load(packages);
+        repo.updated = index.updated;
+
+        Ok(())
+    }
+
+    // Returns the cached zpkg, fetching it first when missing or not matching the index.
+    // Interrupted downloads are left in place for fetchers which can resume them.
+    pub fn fetch(&self, repo: &Repo, package: &Package) -> Result<PathBuf, Error> {
+        let file_name = package.file_name();
+        let path = self.path.join(&file_name);
+
+        if path.is_file() && Self::verify(&path, package).is_ok() {
+            return Ok(path);
+        }
+
+        std::fs::create_dir_all(&self.path)?;
+
+        let fetcher = fetcher_for(&repo.uri, &self.config)?;
+        let tmp_path = self.path.join(format!(".{}.zpstmp", file_name));
+        let resumed = tmp_path.exists();
+
+        let mut result = Self::download(&*fetcher, &file_name, &tmp_path, package);
+
+        // What was resumed may have been left by a different upload, start over once
+        if result.is_err() && resumed && !tmp_path.exists() {
+            result = Self::download(&*fetcher, &file_name, &tmp_path, package);
+        }
+
+        if let Err(err) = result {
+            return Err(anyhow!("failed to fetch {} from {}: {}", file_name, repo.uri, err));
+        }
+
+        std::fs::rename(&tmp_path, &path)?;
+
+        Ok(path)
+    }
+
+    // Downloads which complete but don't verify are discarded
+    fn download(fetcher: &dyn Fetcher, file_name: &str, tmp_path: &Path, package: &Package) -> Result<(), Error> {
+        fetcher.fetch(file_name, tmp_path)?;
+
+        if let Err(err) = Self::verify(tmp_path, package) {
+            let _ = std::fs::remove_file(tmp_path);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    fn verify(path: &Path, package: &Package) -> Result<(), Error> {
+        let size = std::fs::metadata(path)?.len();
+        if size != package.size {
+            return Err(anyhow!("size mismatch, expected {} bytes but got {}", package.size, size));
+        }
+
+        let digest = crate::fs::digest(path)?;
+        if digest != package.digest {
+            return Err(anyhow!("digest mismatch, expected {} but got {}", package.digest, digest));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::zpkg::tests::{build_zpkg, test_root};
+
+    #[test]
+    fn test_cache() -> Result<(), Error> {
+        let root = test_root("zpstestfetcher")?;
+        let zpkg = build_zpkg(&root, "nachos", "1.0.0", &root.join("repo"))?;
+        let index = Index::generate(&root.join("repo"), &root)?;
+
+        let config = Config::for_tree(&root.join("tree"));
+        let cache = Cache::new(&config);
+        let mut repo = Repo::new(Url::from_file_path(root.join("repo")).unwrap(), 10, true);
+
+        // Nothing published yet
+        assert!(cache.refresh(&mut repo).err().unwrap().to_string().starts_with("failed to refresh"));
+        assert!(!cache.load(&mut repo)?);
+
+        index.write(&root.join("repo").join(INDEX))?;
+        cache.refresh(&mut repo)?;
+        assert_eq!(repo.updated, index.updated);
+
+        let package = repo.clone().contents().remove(0);
+        assert_eq!(package.name, "nachos");
+
+        let path = cache.fetch(&repo, &package)?;
+        assert_eq!(path, config.cache_path().join(package.file_name()));
+        assert_eq!(std::fs::read(&path)?, std::fs::read(&zpkg)?);
+
+        // The cached copy is used once verified, even with the repository gone
+        std::fs::rename(root.join("repo"), root.join("offline"))?;
+        assert_eq!(cache.fetch(&repo, &package)?, path);
+
+        let mut offline = Repo::new(repo.uri.clone(), 10, true);
+        assert!(cache.load(&mut offline)?);
+        assert_eq!(offline.contents().len(), 1);
+
+        // A package which doesn't match the index is refused and not cached
+        std::fs::rename(root.join("offline"), root.join("repo"))?;
+        std::fs::write(&zpkg, b"tampered")?;
+        std::fs::write(&path, b"tampered")?;
+
+        let err = cache.fetch(&repo, &package).err().unwrap().to_string();
+        assert!(err.contains("size mismatch"), "{}", err);
+        assert!(!config.cache_path().join(format!(".{}.zpstmp", package.file_name())).exists());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,24 @@
-use std::path::Path;
-use crate::action::{Action, Dir, File};
-use anyhow::Error;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use crate::action::{Action, Dir, File, HardLink, SymLink};
+use anyhow::{anyhow, Error};
 use walkdir::WalkDir;
+use sha3::{Digest, Sha3_256};
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
-use users::{get_user_by_uid, get_group_by_gid, User};
+use users::{get_user_by_uid, get_group_by_gid, get_user_by_name, get_group_by_name, get_effective_uid, User};
 
 pub struct Resolver {}
 
 impl Resolver {
-    pub fn walk(target: &Path, secure: bool) -> Result<Vec<Box<dyn Action>>, Error> {
+    // Ownership is root in secure mode and taken from the tree otherwise, owner and group override both
+    pub fn walk(target: &Path, secure: bool, owner: Option<&str>, group: Option<&str>) -> Result<Vec<Box<dyn Action>>, Error> {
         let mut actions : Vec<Box<dyn Action>> = Vec::new();
 
-        for entry in WalkDir::new(target).into_iter().filter_map(|e| e.ok()) {
+        // First path seen for a shared inode, later paths become links to it
+        let mut inodes: HashMap<(u64, u64), String> = HashMap::new();
+
+        // Sorted so the same path is chosen as the link target on every build
+        for entry in WalkDir::new(target).sort_by(|a, b| a.file_name().cmp(b.file_name())).into_iter().filter_map(|e| e.ok()) {
             if entry.path() == target {
                 continue
             }
@@ -35,6 +42,14 @@ impl Resolver {
                 }
             }
 
+            if let Some(owner) = owner {
+                owner_str = owner.to_string();
+            }
+
+            if let Some(group) = group {
+                group_str = group.to_string();
+            }
+
             if meta.is_dir() {
                 actions.push(Box::new(
                     Dir {
@@ -49,9 +64,35 @@ impl Resolver {
             }
 
             if meta.file_type().is_symlink() {
+                actions.push(Box::new(
+                    SymLink {
+                        path: path.to_str().unwrap().to_string(),
+                        target: std::fs::read_link(entry.path())?.to_str().unwrap().to_string(),
+                        owner: owner_str,
+                        group: group_str
+                    }
+                ));
+
                 continue
             }
 
+            if meta.is_file() && meta.nlink() > 1 {
+                let path = path.to_str().unwrap().to_string();
+
+                if let Some(canonical) = inodes.get(&(meta.dev(), meta.ino())) {
+                    actions.push(Box::new(
+                        HardLink {
+                            path,
+                            target: canonical.clone()
+                        }
+                    ));
+
+                    continue
+                }
+
+                inodes.insert((meta.dev(), meta.ino()), path);
+            }
+
             if meta.is_file() {
                 actions.push(Box::new(
                     File {
@@ -71,4 +112,111 @@ impl Resolver {
 
         Ok(actions)
     }
-}
\ No newline at end of file
+}
+
+// Joins a manifest path to root, refusing anything that would escape it
+pub fn tree_path(root: &Path, path: &str) -> Result<PathBuf, Error> {
+    let mut result = root.to_path_buf();
+
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => result.push(part),
+            Component::CurDir => (),
+            _ => return Err(anyhow!("invalid path outside of tree: {}", path))
+        }
+    }
+
+    if result == root {
+        return Err(anyhow!("invalid empty path: {}", path));
+    }
+
+    Ok(result)
+}
+
+pub fn is_root() -> bool {
+    get_effective_uid() == 0
+}
+
+// Applies ownership by user and group name, does not follow symlinks
+pub fn chown(path: &Path, owner: &str, group: &str) -> Result<(), Error> {
+    chown_in(Path::new("/"), path, owner, group)
+}
+
+// As chown, with names resolved against the passwd and group files of the tree at root
+pub fn chown_in(root: &Path, path: &Path, owner: &str, group: &str) -> Result<(), Error> {
+    let uid = match uid_in(root, owner) {
+        Some(uid) => uid,
+        None => return Err(anyhow!("unknown user {} for {}", owner, path.display()))
+    };
+
+    let gid = match gid_in(root, group) {
+        Some(gid) => gid,
+        None => return Err(anyhow!("unknown group {} for {}", group, path.display()))
+    };
+
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+
+    Ok(())
+}
+
+// Users missing from the tree fall back to the host
+pub fn uid_in(root: &Path, owner: &str) -> Option<u32> {
+    db_id(&root.join("etc/passwd"), owner).or_else(|| get_user_by_name(owner).map(|user| user.uid()))
+}
+
+pub fn gid_in(root: &Path, group: &str) -> Option<u32> {
+    db_id(&root.join("etc/group"), group).or_else(|| get_group_by_name(group).map(|group| group.gid()))
+}
+
+// Both passwd and group files carry the name first and the id third
+fn db_id(db: &Path, name: &str) -> Option<u32> {
+    let contents = std::fs::read_to_string(db).ok()?;
+
+    contents.lines()
+        .map(|line| line.split(':').collect::<Vec<&str>>())
+        .find(|fields| fields.len() > 2 && fields[0] == name)
+        .and_then(|fields| fields[2].parse().ok())
+}
+
+// Digest of file contents as recorded in manifests, empty files have no digest
+pub fn digest(path: &Path) -> Result<String, Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha3_256::new();
+
+    if std::io::copy(&mut file, &mut hasher)? == 0 {
+        return Ok("".to_string());
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn chmod(path: &Path, mode: u32) -> Result<(), Error> {
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_id_in() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestidin");
+        std::fs::create_dir_all(root.join("etc"))?;
+        std::fs::write(root.join("etc/passwd"), "root:x:0:0:root:/root:/bin/sh\nzps:x:4242:4243::/var/lib/zps:/bin/false\n")?;
+        std::fs::write(root.join("etc/group"), "root:x:0:\nzps:x:4243:\n")?;
+
+        assert_eq!(uid_in(&root, "zps"), Some(4242));
+        assert_eq!(gid_in(&root, "zps"), Some(4243));
+        assert_eq!(uid_in(&root, "root"), Some(0));
+
+        // Names the tree doesn't define are looked up on the host
+        assert_eq!(uid_in(&env::temp_dir().join("zpstestidin-missing"), "root"), Some(0));
+        assert_eq!(uid_in(&root, "zps-nobody-here"), None);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::convert::TryFrom;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use byteorder::{LittleEndian, ReadBytesExt};
+use bytes::BufMut;
+use chrono::{DateTime, Utc};
+
+use crate::{Package, Requirement, Version};
+use crate::action::{self, Manifest};
+use crate::platform::{Arch, OS};
+use crate::zpkg::CompType;
+use crate::zpkg::reader::Reader;
+
+pub const INDEX: &str = "zps.index";
+
+const MAGIC: &[u8; 5] = b"zpsi!";
+const VERSION: u8 = 1;
+
+// What a client needs to know about a zpkg without fetching it
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub version: String,
+    pub publisher: String,
+    pub os: String,
+    pub arch: String,
+    pub summary: String,
+    pub description: String,
+
+    #[serde(default)]
+    pub requirements: Vec<action::Requirement>,
+    #[serde(default)]
+    pub channels: Vec<String>,
+
+    pub file_name: String,
+    pub size: u64,
+    pub digest: String,
+}
+
+impl Entry {
+    pub fn new(manifest: &Manifest, file_name: String, size: u64, digest: String) -> Entry {
+        Entry {
+            name: manifest.zpkg.name.clone(),
+            version: manifest.zpkg.version.clone(),
+            publisher: manifest.zpkg.publisher.clone(),
+            os: manifest.zpkg.os.clone(),
+            arch: manifest.zpkg.arch.clone(),
+            summary: manifest.zpkg.summary.clone(),
+            description: manifest.zpkg.description.clone(),
+            requirements: manifest.requirements.clone(),
+            channels: Vec::new(),
+            file_name,
+            size,
+            digest,
+        }
+    }
+
+    // Reads just the manifest, the digest covers the whole file
+    pub fn from_zpkg(path: &Path, work_path: &Path) -> Result<Entry, Error> {
+        let mut reader = Reader::new(path, work_path);
+        reader.read()?;
+
+        let file_name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(anyhow!("invalid zpkg path: {}", path.display())),
+        };
+
+        Ok(Self::new(
+            reader.manifest.as_ref().unwrap(),
+            file_name,
+            std::fs::metadata(path)?.len(),
+            crate::fs::digest(path)?,
+        ))
+    }
+
+    pub(crate) fn package(&self) -> Result<Package, Error> {
+        let mut package = Package::new(
+            self.name.clone(),
+            Version::from(self.version.as_str())?,
+            self.publisher.clone(),
+            OS::from_str(&self.os)?,
+            Arch::from_str(&self.arch)?,
+            self.summary.clone(),
+            self.description.clone(),
+        );
+
+        package.requirements = self.requirements.iter()
+            .map(|r| Requirement::from_action(r).map(Box::new))
+            .collect::<Result<Vec<Box<Requirement>>, Error>>()?;
+        package.channels = self.channels.iter().map(|c| Box::new(c.clone())).collect();
+        package.size = self.size;
+        package.digest = self.digest.clone();
+
+        Ok(package)
+    }
+}
+
+/// Repository metadata, stored as a small header followed by compressed JSON.
+///
+/// ```text
+/// magic "zpsi!" | version u8 | compression u8 | json length u32 le | compressed json
+/// ```
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Index {
+    pub updated: DateTime<Utc>,
+    pub packages: Vec<Entry>,
+}
+
+impl Index {
+    pub fn new() -> Index {
+        Index {
+            updated: Utc::now(),
+            packages: Vec::new(),
+        }
+    }
+
+    // Indexes every zpkg in dir, other files are ignored
+    pub fn generate(dir: &Path, work_path: &Path) -> Result<Index, Error> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "zpkg"))
+            .collect();
+
+        paths.sort();
+
+        let mut index = Self::new();
+
+        for path in paths.iter() {
+            let entry = Entry::from_zpkg(path, work_path)
+                .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+
+            index.packages.push(entry);
+        }
+
+        Ok(index)
+    }
+
+    pub fn read(path: &Path) -> Result<Index, Error> {
+        Self::from_bytes(&std::fs::read(path)?)
+            .map_err(|err| anyhow!("{}: {}", path.display(), err))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Index, Error> {
+        let mut cursor = Cursor::new(bytes);
+
+        let mut magic = [0u8; MAGIC.len()];
+        cursor.read_exact(&mut magic).map_err(|_| anyhow!("repository index is truncated"))?;
+
+        if &magic != MAGIC {
+            return Err(anyhow!("not a repository index, invalid magic"));
+        }
+
+        let version = cursor.read_u8().map_err(|_| anyhow!("repository index is truncated"))?;
+        if version != VERSION {
+            return Err(anyhow!("unknown repository index version: {}", version));
+        }
+
+        let compression = cursor.read_u8().map_err(|_| anyhow!("repository index is truncated"))?;
+        let compression = CompType::try_from(compression)
+            .map_err(|_| anyhow!("unknown repository index compression type: {}", compression))?;
+        let len = cursor.read_u32::<LittleEndian>().map_err(|_| anyhow!("repository index is truncated"))?;
+
+        let body = &bytes[cursor.position() as usize..];
+
+        let json = match compression {
+            CompType::ZSTD => zstd::block::decompress(body, len as usize)
+                .map_err(|err| anyhow!("invalid repository index: {}", err))?,
+        };
+
+        serde_json::from_slice(&json).map_err(|err| anyhow!("invalid repository index: {}", err))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let json = serde_json::to_vec(self)?;
+
+        let mut buf: Vec<u8> = Vec::new();
+        buf.put_slice(MAGIC);
+        buf.put_u8(VERSION);
+        buf.put_u8(CompType::ZSTD as u8);
+        buf.put_u32_le(json.len() as u32);
+        buf.put_slice(&zstd::block::compress(&json, 3)?);
+
+        Ok(buf)
+    }
+
+    // Readers never see a partially written index
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let tmp_path = path.with_extension("tmp");
+
+        std::fs::write(&tmp_path, self.to_bytes()?)?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn packages(&self) -> Result<Vec<Package>, Error> {
+        self.packages.iter()
+            .map(|entry| entry.package().map_err(|err| anyhow!("invalid index entry {}: {}", entry.file_name, err)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Repo;
+    use crate::zpkg::tests::{build, test_root, zpkgfile};
+    use url::Url;
+
+    #[test]
+    fn test_index() -> Result<(), Error> {
+        let root = test_root("zpstestindex")?;
+        std::fs::create_dir_all(root.join("repo"))?;
+        std::fs::write(root.join("repo/README"), "not a zpkg")?;
+
+        for (name, requirement) in vec![("nachos", "Requirement \"salsa\" {}"), ("salsa", "")] {
+            build(&root, &format!("{}\n{}\n", zpkgfile(name, "1.0.0"), requirement), &root.join("repo"))?;
+        }
+
+        let index = Index::generate(&root.join("repo"), &root)?;
+        assert_eq!(index.packages.len(), 2);
+        assert_eq!(index.packages[0].name, "nachos");
+        assert_eq!(index.packages[0].requirements[0].name, "salsa");
+
+        let zpkg = root.join("repo").join(&index.packages[1].file_name);
+        assert_eq!(index.packages[1].size, std::fs::metadata(&zpkg)?.len());
+        assert_eq!(index.packages[1].digest, crate::fs::digest(&zpkg)?);
+
+        index.write(&root.join("repo").join(INDEX))?;
+        let read = Index::read(&root.join("repo").join(INDEX))?;
+        assert!(read.packages == index.packages);
+        assert_eq!(read.updated, index.updated);
+
+        let mut repo = Repo::new(Url::parse("file:///tmp/zpstestindex/repo").unwrap(), 10, true);
+        repo.load(read.packages()?);
+
+        let contents = repo.contents();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[0].file_name(), index.packages[0].file_name);
+        assert_eq!(contents[0].requirements[0].to_string(), "salsa");
+        assert_eq!(contents[1].digest, index.packages[1].digest);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_errors() -> Result<(), Error> {
+        let bytes = Index::new().to_bytes()?;
+
+        let mut version = bytes.clone();
+        version[5] = 9;
+
+        let mut compression = bytes.clone();
+        compression[6] = 9;
+
+        let errors = vec![
+            (b"zps".to_vec(), "repository index is truncated"),
+            (b"zpkg!\x01".to_vec(), "not a repository index, invalid magic"),
+            (version, "unknown repository index version: 9"),
+            (compression, "unknown repository index compression type: 9"),
+            (bytes[..bytes.len() - 1].to_vec(), "invalid repository index"),
+        ];
+
+        for (bytes, expected) in errors {
+            let err = Index::from_bytes(&bytes).err().unwrap().to_string();
+            assert!(err.starts_with(expected), "{}", err);
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::io::{Read, Write};
 use std::io;
 
 pub struct MultiWriter<'a> {
@@ -27,4 +27,24 @@ impl<'a> Write for MultiWriter<'a>  {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+pub struct TeeReader<R: Read, W: Write> {
+    reader: R,
+    writer: W
+}
+
+impl<R: Read, W: Write> TeeReader<R, W> {
+    pub fn new(reader: R, writer: W) -> TeeReader<R, W> {
+        TeeReader { reader, writer }
+    }
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.writer.write_all(&buf[..n])?;
+
+        Ok(n)
+    }
+}
@@ -0,0 +1,281 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::collections::BTreeSet;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+
+use crate::action::Manifest;
+use crate::config::Config;
+use crate::db::State;
+use crate::fs;
+
+const JOURNAL: &str = "journal";
+
+// What was at a path before the transaction touched it
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+enum Entry {
+    Absent { path: String },
+    Dir { path: String, mode: u32, uid: u32, gid: u32 },
+    Saved { path: String, backup: PathBuf, mode: u32, uid: u32, gid: u32 },
+}
+
+// Written before a transaction modifies the tree, holds enough to put the tree and package state back
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Journal {
+    tree: PathBuf,
+    entries: Vec<Entry>,
+    packages: Vec<(String, Option<Manifest>)>,
+
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    backup_path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(config: &Config) -> Journal {
+        Journal {
+            tree: config.tree(),
+            entries: Vec::new(),
+            packages: Vec::new(),
+            path: config.data_path().join(JOURNAL),
+            backup_path: config.tmp_path().join(JOURNAL),
+        }
+    }
+
+    pub fn pending(&self) -> bool {
+        self.path.exists()
+    }
+
+    // Rolls back a transaction that never committed, returns whether there was one
+    pub fn recover(&mut self, state: &mut State) -> Result<bool, Error> {
+        if !self.pending() {
+            return Ok(false);
+        }
+
+        let journal: Journal = serde_json::from_slice(&std::fs::read(&self.path)?)
+            .map_err(|err| anyhow!("invalid journal {}: {}", self.path.display(), err))?;
+
+        self.tree = journal.tree;
+        self.entries = journal.entries;
+        self.packages = journal.packages;
+        self.rollback(state)?;
+
+        Ok(true)
+    }
+
+    // Saves the current state of every path and package, then persists the journal
+    pub fn record(&mut self, paths: &BTreeSet<String>, packages: Vec<(String, Option<Manifest>)>) -> Result<(), Error> {
+        let _ = std::fs::remove_dir_all(&self.backup_path);
+        std::fs::create_dir_all(&self.backup_path)?;
+
+        // Parents sort before children, rollback walks the entries in reverse
+        for (index, path) in paths.iter().enumerate() {
+            let entry = self.save(path, index)?;
+            self.entries.push(entry);
+        }
+
+        self.packages = packages;
+
+        std::fs::create_dir_all(self.path.parent().unwrap())?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        let tmp = std::fs::File::create(&tmp_path)?;
+        serde_json::to_writer(&tmp, &self)?;
+        tmp.sync_all()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    fn save(&self, path: &str, index: usize) -> Result<Entry, Error> {
+        let target = fs::tree_path(&self.tree, path)?;
+
+        let meta = match std::fs::symlink_metadata(&target) {
+            Ok(meta) => meta,
+            Err(_) => return Ok(Entry::Absent { path: path.to_string() }),
+        };
+
+        if meta.is_dir() {
+            return Ok(Entry::Dir { path: path.to_string(), mode: meta.mode() & 0o7777, uid: meta.uid(), gid: meta.gid() });
+        }
+
+        let backup = self.backup_path.join(index.to_string());
+
+        // Providers replace paths rather than writing in place, so a link to the inode is a full backup
+        if meta.file_type().is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(&target)?, &backup)?;
+        } else if std::fs::hard_link(&target, &backup).is_err() {
+            std::fs::copy(&target, &backup)?;
+        }
+
+        Ok(Entry::Saved { path: path.to_string(), backup, mode: meta.mode() & 0o7777, uid: meta.uid(), gid: meta.gid() })
+    }
+
+    pub fn rollback(&mut self, state: &mut State) -> Result<(), Error> {
+        for entry in self.entries.iter().rev() {
+            match entry {
+                Entry::Absent { path } => {
+                    let target = fs::tree_path(&self.tree, path)?;
+
+                    match std::fs::symlink_metadata(&target) {
+                        Ok(meta) if meta.is_dir() => { let _ = std::fs::remove_dir(&target); },
+                        Ok(_) => std::fs::remove_file(&target)?,
+                        Err(_) => (),
+                    }
+                },
+                Entry::Dir { path, mode, uid, gid } => {
+                    let target = fs::tree_path(&self.tree, path)?;
+
+                    std::fs::create_dir_all(&target)?;
+                    std::fs::set_permissions(&target, std::fs::Permissions::from_mode(*mode))?;
+                    Self::restore_owner(&target, *uid, *gid)?;
+                },
+                Entry::Saved { path, backup, mode, uid, gid } => {
+                    let target = fs::tree_path(&self.tree, path)?;
+
+                    if let Ok(meta) = std::fs::symlink_metadata(&target) {
+                        match meta.is_dir() {
+                            true => std::fs::remove_dir_all(&target)?,
+                            false => std::fs::remove_file(&target)?,
+                        }
+                    }
+
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+
+                    Self::restore(backup, &target)?;
+
+                    if !std::fs::symlink_metadata(&target)?.file_type().is_symlink() {
+                        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(*mode))?;
+                    }
+                    Self::restore_owner(&target, *uid, *gid)?;
+                },
+            }
+        }
+
+        for (name, manifest) in self.packages.iter() {
+            match manifest {
+                Some(manifest) => state.pkg_put(manifest.clone())?,
+                None => state.pkg_del(name.clone())?,
+            }
+        }
+
+        self.commit()
+    }
+
+    // The transaction is complete, backups are no longer needed
+    pub fn commit(&mut self) -> Result<(), Error> {
+        if self.path.exists() {
+            std::fs::remove_file(&self.path)?;
+        }
+
+        let _ = std::fs::remove_dir_all(&self.backup_path);
+        self.entries.clear();
+        self.packages.clear();
+
+        Ok(())
+    }
+
+    // Backups live under the tree, falling back to a copy should tmp be a separate mount
+    fn restore(backup: &Path, target: &Path) -> Result<(), Error> {
+        if std::fs::rename(backup, target).is_ok() {
+            return Ok(());
+        }
+
+        match std::fs::symlink_metadata(backup)?.file_type().is_symlink() {
+            true => std::os::unix::fs::symlink(std::fs::read_link(backup)?, target)?,
+            false => { std::fs::copy(backup, target)?; },
+        }
+
+        Ok(())
+    }
+
+    fn restore_owner(target: &Path, uid: u32, gid: u32) -> Result<(), Error> {
+        if fs::is_root() {
+            std::os::unix::fs::lchown(target, Some(uid), Some(gid))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Zpkg;
+    use std::env;
+
+    fn manifest(name: &str) -> Manifest {
+        Manifest::new(Zpkg {
+            name: name.to_string(),
+            version: "1.0.0:20200320T221640Z".to_string(),
+            publisher: "zps.io".to_string(),
+            arch: "x86_64".to_string(),
+            os: "linux".to_string(),
+            summary: "Test zpkg".to_string(),
+            description: "Test zpkg".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_rollback() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestjournal");
+        let _ = std::fs::remove_dir_all(&root);
+        let config = Config::for_tree(&root);
+        let tree = config.tree();
+
+        std::fs::create_dir_all(tree.join("etc"))?;
+        std::fs::write(tree.join("etc/nachos.conf"), "old")?;
+        std::fs::set_permissions(tree.join("etc/nachos.conf"), std::fs::Permissions::from_mode(0o600))?;
+        std::os::unix::fs::symlink("nachos.conf", tree.join("etc/link"))?;
+
+        let mut state = State::new(config.db_path().to_str().unwrap());
+        state.pkg_put(manifest("nachos"))?;
+
+        let paths: BTreeSet<String> = vec!["etc", "etc/nachos.conf", "etc/link", "etc/salsa", "etc/salsa/salsa.conf"]
+            .into_iter().map(|p| p.to_string()).collect();
+
+        let mut journal = Journal::new(&config);
+        journal.record(&paths, vec![("nachos".to_string(), Some(manifest("nachos"))), ("salsa".to_string(), None)])?;
+        assert!(Journal::new(&config).pending());
+
+        // Simulate a transaction which died halfway through
+        std::fs::remove_file(tree.join("etc/nachos.conf"))?;
+        std::fs::write(tree.join("etc/nachos.conf"), "new")?;
+        std::fs::remove_file(tree.join("etc/link"))?;
+        std::fs::create_dir_all(tree.join("etc/salsa"))?;
+        std::fs::write(tree.join("etc/salsa/salsa.conf"), "salsa")?;
+        state.pkg_del("nachos".to_string())?;
+        state.pkg_put(manifest("salsa"))?;
+        drop(journal);
+
+        assert!(Journal::new(&config).recover(&mut state)?);
+
+        assert_eq!(std::fs::read(tree.join("etc/nachos.conf"))?, b"old");
+        assert_eq!(std::fs::metadata(tree.join("etc/nachos.conf"))?.permissions().mode() & 0o7777, 0o600);
+        assert_eq!(std::fs::read_link(tree.join("etc/link"))?.to_str().unwrap(), "nachos.conf");
+        assert!(!tree.join("etc/salsa").exists());
+
+        let installed: Vec<String> = state.pkg_list()?.into_iter().map(|m| m.zpkg.name).collect();
+        assert_eq!(installed, vec!["nachos".to_string()]);
+
+        assert!(!Journal::new(&config).pending());
+        assert!(!config.tmp_path().join(JOURNAL).exists());
+        assert!(!Journal::new(&config).recover(&mut state)?);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -13,9 +13,16 @@ pub mod app;
 pub mod config;
 pub mod console;
 mod db;
+mod fetcher;
+pub mod index;
+mod journal;
 mod platform;
 mod provider;
+pub mod publisher;
+mod solver;
+mod transaction;
 pub mod zpkg;
+mod zpf;
 pub mod fs;
 pub mod io;
 
@@ -71,11 +78,10 @@ impl Version {
 
         let semver = semver::Version::parse(parts[0])?;
 
+        // Without a timestamp the version stands for every build of it
         let mut time = None;
         if parts.len() == 2 {
             time = Some(Utc.datetime_from_str(parts[1], "%Y%m%dT%H%M%SZ")?);
-        } else {
-            time = Some(Utc::now())
         }
 
         Ok(Version { semver, time })
@@ -145,10 +151,12 @@ impl PartialEq for Version {
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug)]
 enum Comparator {
     ANY,
+    GT,
     GTE,
+    LT,
     LTE,
     EQ,
     EXQ,
@@ -158,7 +166,9 @@ impl Display for Comparator {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Comparator::ANY => write!(f, "{}", String::from("ANY")),
+            Comparator::GT => write!(f, "{}", String::from("GT")),
             Comparator::GTE => write!(f, "{}", String::from("GTE")),
+            Comparator::LT => write!(f, "{}", String::from("LT")),
             Comparator::LTE => write!(f, "{}", String::from("LTE")),
             Comparator::EQ => write!(f, "{}", String::from("EQ")),
             Comparator::EXQ => write!(f, "{}", String::from("EXQ")),
@@ -166,14 +176,54 @@ impl Display for Comparator {
     }
 }
 
-#[derive(Clone, PartialEq)]
+impl FromStr for Comparator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ANY" => Ok(Comparator::ANY),
+            "GT" => Ok(Comparator::GT),
+            "GTE" => Ok(Comparator::GTE),
+            "LT" => Ok(Comparator::LT),
+            "LTE" => Ok(Comparator::LTE),
+            "EQ" => Ok(Comparator::EQ),
+            "EXQ" => Ok(Comparator::EXQ),
+            _ => Err(anyhow!("invalid comparator: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 enum RequirementMethod {
     Depends,
     Provides,
     Conflicts,
 }
 
-#[derive(Clone)]
+impl Display for RequirementMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequirementMethod::Depends => write!(f, "{}", String::from("depends")),
+            RequirementMethod::Provides => write!(f, "{}", String::from("provides")),
+            RequirementMethod::Conflicts => write!(f, "{}", String::from("conflicts")),
+        }
+    }
+}
+
+impl FromStr for RequirementMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depends" => Ok(RequirementMethod::Depends),
+            "provides" => Ok(RequirementMethod::Provides),
+            "conflicts" => Ok(RequirementMethod::Conflicts),
+            _ => Err(anyhow!("invalid requirement method: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 struct Requirement {
     name: String,
     method: RequirementMethod,
@@ -196,6 +246,21 @@ impl Requirement {
         }
     }
 
+    // Manifest requirements carry the method and comparator by name, ANY is the only one without a version
+    pub fn from_action(action: &action::Requirement) -> Result<Requirement, Error> {
+        let method = RequirementMethod::from_str(&action.method)?;
+        let comparator = Comparator::from_str(&action.operation)?;
+
+        let version = match (&comparator, action.version.is_empty()) {
+            (Comparator::ANY, true) => None,
+            (Comparator::ANY, false) => return Err(anyhow!("requirement {} with comparator ANY takes no version", action.name)),
+            (_, true) => return Err(anyhow!("requirement {} with comparator {} requires a version", action.name, comparator)),
+            (_, false) => Some(Version::from(action.version.as_str())?),
+        };
+
+        Ok(Requirement::new(action.name.clone(), method, comparator, version))
+    }
+
     pub fn from_simple<S: Into<String>>(requirement: S) -> Result<Requirement, Error> {
         let requirement_into = requirement.into();
 
@@ -227,6 +292,133 @@ impl Requirement {
             }),
         }
     }
+
+    // A name followed by comma joined constraints, e.g. "zps>=1.2, <2" or "zps^1", tilde and caret
+    // expand to a pair of bounds so the result holds every requirement a package must satisfy
+    pub fn parse<S: Into<String>>(requirement: S) -> Result<Vec<Requirement>, Error> {
+        let requirement_into = requirement.into();
+
+        let start = requirement_into.find(|c| "@=<>~^".contains(c)).unwrap_or(requirement_into.len());
+        let name = requirement_into[..start].trim();
+
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ',') {
+            return Err(anyhow!("invalid requirement: {}", requirement_into));
+        }
+
+        let constraints = requirement_into[start..].trim();
+
+        if constraints.is_empty() {
+            return Ok(vec![Requirement::new(name.to_string(), RequirementMethod::Depends, Comparator::ANY, None)]);
+        }
+
+        let mut requirements: Vec<Requirement> = Vec::new();
+
+        for constraint in constraints.split(',') {
+            let parsed = Self::constraint(name, constraint.trim())
+                .map_err(|err| anyhow!("invalid requirement {}: {}", requirement_into, err))?;
+
+            requirements.extend(parsed);
+        }
+
+        Ok(requirements)
+    }
+
+    fn constraint(name: &str, constraint: &str) -> Result<Vec<Requirement>, Error> {
+        let operator = match [">=", "<=", "==", ">", "<", "=", "@", "~", "^"].iter().find(|op| constraint.starts_with(*op)) {
+            Some(operator) => *operator,
+            None if constraint.is_empty() => return Err(anyhow!("empty constraint")),
+            None => return Err(anyhow!("unknown operator in '{}'", constraint)),
+        };
+
+        let raw = constraint[operator.len()..].trim();
+
+        if raw.is_empty() {
+            return Err(anyhow!("missing version in '{}'", constraint));
+        }
+
+        let (version, components) = Self::partial(raw)?;
+
+        let bound = |comparator: Comparator, version: Version| {
+            Requirement::new(name.to_string(), RequirementMethod::Depends, comparator, Some(version))
+        };
+
+        match operator {
+            ">=" => Ok(vec![bound(Comparator::GTE, version)]),
+            "<=" => Ok(vec![bound(Comparator::LTE, version)]),
+            ">" => Ok(vec![bound(Comparator::GT, version)]),
+            "<" => Ok(vec![bound(Comparator::LT, version)]),
+            "~" | "^" => {
+                if version.time.is_some() {
+                    return Err(anyhow!("'{}' does not accept a timestamp", operator));
+                }
+
+                let upper = Self::upper(operator, &version.semver, components);
+
+                Ok(vec![bound(Comparator::GTE, version), bound(Comparator::LT, upper)])
+            },
+            _ => match version.time {
+                Some(_) => Ok(vec![bound(Comparator::EXQ, version)]),
+                None => Ok(vec![bound(Comparator::EQ, version)]),
+            },
+        }
+    }
+
+    // Missing minor and patch components read as zero, also returns how many were given
+    fn partial(raw: &str) -> Result<(Version, usize), Error> {
+        if raw.contains(':') {
+            return Ok((Version::from(raw)?, 3));
+        }
+
+        let end = raw.find(|c| c == '-' || c == '+').unwrap_or(raw.len());
+        let components = raw[..end].split('.').count();
+
+        if components > 3 {
+            return Err(anyhow!("invalid version string: {}", raw));
+        }
+
+        let padded = format!("{}{}{}", &raw[..end], ".0".repeat(3 - components), &raw[end..]);
+
+        Ok((Version::from(padded)?, components))
+    }
+
+    // Exclusive upper bound, tilde allows patch updates and caret anything left of the first non-zero component
+    fn upper(operator: &str, semver: &semver::Version, components: usize) -> Version {
+        let upper = match operator {
+            "~" if components == 1 => semver::Version::new(semver.major + 1, 0, 0),
+            "~" => semver::Version::new(semver.major, semver.minor + 1, 0),
+            _ if semver.major > 0 || components == 1 => semver::Version::new(semver.major + 1, 0, 0),
+            _ if semver.minor > 0 || components == 2 => semver::Version::new(0, semver.minor + 1, 0),
+            _ => semver::Version::new(0, 0, semver.patch + 1),
+        };
+
+        Version { semver: upper, time: None }
+    }
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.comparator, &self.version) {
+            (Comparator::GT, Some(version)) => write!(f, "{}>{}", self.name, version.semver),
+            (Comparator::GTE, Some(version)) => write!(f, "{}>={}", self.name, version.semver),
+            (Comparator::LT, Some(version)) => write!(f, "{}<{}", self.name, version.semver),
+            (Comparator::LTE, Some(version)) => write!(f, "{}<={}", self.name, version.semver),
+            (Comparator::EQ, Some(version)) => write!(f, "{}@{}", self.name, version.semver),
+            (Comparator::EXQ, Some(version)) => write!(f, "{}@{}", self.name, version),
+            _ => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl serde::Serialize for Requirement {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Requirement", 3)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("method", &self.method.to_string())?;
+        state.serialize_field("constraint", &self.to_string())?;
+        state.end()
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -329,6 +521,10 @@ struct Package {
 
     location: i32,
     priority: i32,
+
+    // Known once the package is in a repository index
+    size: u64,
+    digest: String,
 }
 
 impl Package {
@@ -353,6 +549,8 @@ impl Package {
             channels: Vec::default(),
             location: 0,
             priority: 10,
+            size: 0,
+            digest: String::default(),
         }
     }
     
@@ -365,10 +563,14 @@ impl Package {
             arch: Arch::from_str(&manifest.zpkg.arch)?,
             summary: manifest.zpkg.summary,
             description: manifest.zpkg.description,
-            requirements: vec![],
+            requirements: manifest.requirements.iter()
+                .map(|r| Requirement::from_action(r).map(Box::new))
+                .collect::<Result<Vec<Box<Requirement>>, Error>>()?,
             channels: vec![],
             location: 0,
-            priority: 10
+            priority: 10,
+            size: 0,
+            digest: String::default(),
         })
     }
 
@@ -385,29 +587,60 @@ impl Package {
 
     fn satisfies(&self, req: Requirement) -> bool {
         if self.name != req.name {
-            return false;
+            return self.provides(&req);
         }
 
-        match req.comparator {
+        Self::matches(&self.version, &req)
+    }
+
+    // Provided names satisfy any requirement on them, versioned requirements only when a version is provided
+    fn provides(&self, req: &Requirement) -> bool {
+        self.requirements.iter()
+            .filter(|r| r.method == RequirementMethod::Provides && r.name == req.name)
+            .any(|r| match (&req.comparator, &r.version) {
+                (Comparator::ANY, _) => true,
+                (_, Some(version)) => Self::matches(version, req),
+                (_, None) => false,
+            })
+    }
+
+    fn matches(version: &Version, req: &Requirement) -> bool {
+        match &req.comparator {
             Comparator::ANY => true,
-            Comparator::EXQ => match req.version {
-                Some(v) => self.version.exq(&v),
+            Comparator::EXQ => match &req.version {
+                Some(v) => version.exq(v),
                 None => false,
             },
-            Comparator::GTE => match req.version {
-                Some(v) => self.version >= v,
+            Comparator::GT => match &req.version {
+                Some(v) => Self::compare(version, v) == Ordering::Greater,
                 None => false,
             },
-            Comparator::EQ => match req.version {
-                Some(v) => self.version == v,
+            Comparator::GTE => match &req.version {
+                Some(v) => Self::compare(version, v) != Ordering::Less,
                 None => false,
             },
-            Comparator::LTE => match req.version {
-                Some(v) => self.version <= v,
+            Comparator::EQ => match &req.version {
+                Some(v) => version == v,
+                None => false,
+            },
+            Comparator::LT => match &req.version {
+                Some(v) => Self::compare(version, v) == Ordering::Less,
+                None => false,
+            },
+            Comparator::LTE => match &req.version {
+                Some(v) => Self::compare(version, v) != Ordering::Greater,
                 None => false,
             },
         }
     }
+
+    // Versions without a timestamp compare against every build of them alike
+    fn compare(version: &Version, other: &Version) -> Ordering {
+        match other.time {
+            Some(_) => version.cmp(other),
+            None => version.semver.cmp(&other.semver),
+        }
+    }
 }
 
 impl Ord for Package {
@@ -530,7 +763,9 @@ impl Repo {
         names.dedup();
 
         for name in names {
-            let packages = self.contents_for_name(name);
+            // Oldest first, so those beyond the count are dropped from the front
+            let mut packages = self.contents_for_name(name);
+            packages.sort();
 
             let mut current: VecDeque<Package> = VecDeque::from_iter(packages);
 
@@ -716,6 +951,135 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_requirement_parse() -> Result<(), Error> {
+        let cases = vec![
+            ("zps", vec!["ANY zps"]),
+            ("zps>=1.2", vec!["GTE zps>=1.2.0"]),
+            ("zps<2.0", vec!["LT zps<2.0.0"]),
+            ("zps>1.0.0", vec!["GT zps>1.0.0"]),
+            ("zps<=3", vec!["LTE zps<=3.0.0"]),
+            ("zps=1.2.3", vec!["EQ zps@1.2.3"]),
+            ("zps==1.2.3", vec!["EQ zps@1.2.3"]),
+            ("zps@1.2", vec!["EQ zps@1.2.0"]),
+            ("zps@1.2.3:20200415T194203Z", vec!["EXQ zps@1.2.3:20200415T194203Z"]),
+            ("zps>=1.0.0-beta.1", vec!["GTE zps>=1.0.0-beta.1"]),
+            ("zps~1.4", vec!["GTE zps>=1.4.0", "LT zps<1.5.0"]),
+            ("zps~1.4.2", vec!["GTE zps>=1.4.2", "LT zps<1.5.0"]),
+            ("zps~1", vec!["GTE zps>=1.0.0", "LT zps<2.0.0"]),
+            ("zps^1", vec!["GTE zps>=1.0.0", "LT zps<2.0.0"]),
+            ("zps^1.2.3", vec!["GTE zps>=1.2.3", "LT zps<2.0.0"]),
+            ("zps^0.3", vec!["GTE zps>=0.3.0", "LT zps<0.4.0"]),
+            ("zps^0.0.3", vec!["GTE zps>=0.0.3", "LT zps<0.0.4"]),
+            ("zps^0.0", vec!["GTE zps>=0.0.0", "LT zps<0.1.0"]),
+            ("zps ^0", vec!["GTE zps>=0.0.0", "LT zps<1.0.0"]),
+            ("zps>=1.2, <2.0", vec!["GTE zps>=1.2.0", "LT zps<2.0.0"]),
+            (" zps >= 1.2 , < 2 ", vec!["GTE zps>=1.2.0", "LT zps<2.0.0"]),
+            ("zps~1.4, >1.4.1, <=1.4.9", vec!["GTE zps>=1.4.0", "LT zps<1.5.0", "GT zps>1.4.1", "LTE zps<=1.4.9"]),
+        ];
+
+        for (requirement, expected) in cases {
+            let parsed: Vec<String> = Requirement::parse(requirement)?
+                .iter()
+                .map(|r| format!("{} {}", r.comparator, r))
+                .collect();
+            assert_eq!(parsed, expected, "{}", requirement);
+        }
+
+        let errors = vec![
+            ("", "invalid requirement: "),
+            (">=1.0", "invalid requirement: >=1.0"),
+            ("zps snarf>=1", "invalid requirement: zps snarf>=1"),
+            ("zps>=", "invalid requirement zps>=: missing version in '>='"),
+            ("zps>=1.0,", "invalid requirement zps>=1.0,: empty constraint"),
+            ("zps>=1.0, !2", "invalid requirement zps>=1.0, !2: unknown operator in '!2'"),
+            ("zps>=1.2.3.4", "invalid requirement zps>=1.2.3.4: invalid version string: 1.2.3.4"),
+            ("zps~1.2.3:20200415T194203Z", "invalid requirement zps~1.2.3:20200415T194203Z: '~' does not accept a timestamp"),
+        ];
+
+        for (requirement, expected) in errors {
+            assert_eq!(Requirement::parse(requirement).err().unwrap().to_string(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_requirement_satisfies() -> Result<(), Error> {
+        let zps = Package::new(
+            String::from("zps"),
+            Version::from("1.4.2:20200415T194203Z").unwrap(),
+            String::from("zps.io"),
+            OS::Linux,
+            Arch::X8664,
+            String::from("zps"),
+            String::from("zps"),
+        );
+
+        let cases = vec![
+            ("zps", true),
+            ("snarf", false),
+            ("zps~1.4", true),
+            ("zps~1.3", false),
+            ("zps^1.5", false),
+            ("zps^1", true),
+            ("zps^0.4", false),
+            ("zps@1.4.2", true),
+            ("zps<=1.4.2", true),
+            ("zps<1.4.2", false),
+            ("zps>1.4.1", true),
+            ("zps>1.4.2", false),
+            ("zps>=1.4.2:20200415T194203Z", true),
+            ("zps>1.4.2:20200415T194203Z", false),
+            ("zps<1.4.2:20200515T194203Z", true),
+            ("zps@1.4.2:20200515T194203Z", false),
+            ("zps>=1.0, <2", true),
+            ("zps>=1.0, <1.4", false),
+        ];
+
+        for (requirement, expected) in cases {
+            let satisfied = Requirement::parse(requirement)?.into_iter().all(|r| zps.satisfies(r));
+            assert_eq!(satisfied, expected, "{}", requirement);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_provides() -> Result<(), Error> {
+        let mut postfix = Package::new(
+            String::from("postfix"),
+            Version::from("3.4.0:20200415T194203Z").unwrap(),
+            String::from("zps.io"),
+            OS::Linux,
+            Arch::X8664,
+            String::from("postfix"),
+            String::from("postfix"),
+        );
+
+        postfix.requirements = vec![
+            Box::new(Requirement::new(String::from("mail-transport-agent"), RequirementMethod::Provides, Comparator::ANY, None)),
+            Box::new(Requirement::new(String::from("sendmail-bin"), RequirementMethod::Provides, Comparator::EQ, Some(Version::from("8.15.0")?))),
+        ];
+
+        let cases = vec![
+            ("postfix^3", true),
+            ("mail-transport-agent", true),
+            ("mail-transport-agent>=1", false),
+            ("sendmail-bin", true),
+            ("sendmail-bin>=8.0", true),
+            ("sendmail-bin<8.15", false),
+            ("exim", false),
+        ];
+
+        for (requirement, expected) in cases {
+            let satisfied = Requirement::parse(requirement)?.into_iter().all(|r| postfix.satisfies(r));
+            assert_eq!(satisfied, expected, "{}", requirement);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_request() {
         let mut req = Request::new();
@@ -956,4 +1320,32 @@ mod tests {
         assert_eq!("zps@1.3.4:20200415T194203Z", contents.get(2).unwrap().id());
         assert_eq!("zps@1.3.5:20200415T194203Z", contents.get(3).unwrap().id());
     }
+
+    #[test]
+    fn test_repo_prune() {
+        let mut repo = Repo::new(Url::parse("s3://somepath/zps.io/core").unwrap(), 8, true);
+
+        let packages: Vec<Package> = vec!["1.3.6", "1.3.4", "1.3.5"].iter().map(|version| Package::new(
+            String::from("zps"),
+            Version::from(format!("{}:20200415T194203Z", version).as_str()).unwrap(),
+            String::from("zps.io"),
+            OS::Linux,
+            Arch::X8664,
+            String::from("zps the last word"),
+            String::from("zps the last word"),
+        )).collect();
+
+        assert!(repo.add(&packages).is_empty());
+        assert_eq!(repo.add(&packages[..1]).len(), 1);
+        assert!(repo.prune(3).is_none());
+
+        let pruned = repo.prune(1).unwrap();
+        assert_eq!(pruned.len(), 2);
+        assert_eq!("zps@1.3.4:20200415T194203Z", pruned[0].id());
+        assert_eq!("zps@1.3.5:20200415T194203Z", pruned[1].id());
+
+        let contents = repo.contents();
+        assert_eq!(contents.len(), 1);
+        assert_eq!("zps@1.3.6:20200415T194203Z", contents[0].id());
+    }
 }
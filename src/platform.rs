@@ -10,6 +10,9 @@
 
 use std::fmt;
 use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
 
 use strum::IntoEnumIterator;
 use strum_macros::{EnumIter, EnumString};
@@ -34,7 +37,7 @@ pub enum Arch {
     X8664,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct OSArch {
     os: OS,
     arch: Arch,
@@ -66,6 +69,24 @@ impl Display for OSArch {
     }
 }
 
+impl FromStr for OSArch {
+    type Err = Error;
+
+    // Arch names may contain underscores but never dashes, e.g. linux-x86_64
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.splitn(2, '-').collect();
+
+        if parts.len() != 2 {
+            return Err(anyhow!("invalid platform: {}", s));
+        }
+
+        let os = OS::from_str(parts[0]).map_err(|_| anyhow!("invalid os: {}", parts[0]))?;
+        let arch = Arch::from_str(parts[1]).map_err(|_| anyhow!("invalid arch: {}", parts[1]))?;
+
+        Ok(OSArch { os, arch })
+    }
+}
+
 impl OSArch {
     pub fn new(os: OS, arch: Arch) -> OSArch {
         OSArch { os, arch }
@@ -144,6 +165,21 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_from_str() -> Result<(), Error> {
+        assert_eq!(
+            OSArch::from_str("linux-x86_64")?,
+            OSArch::new(OS::Linux, Arch::X8664)
+        );
+        assert_eq!(
+            OSArch::from_str("any-arm64")?,
+            OSArch::new(OS::Any, Arch::Arm64)
+        );
+        assert!(OSArch::from_str("linux").is_err());
+        assert!(OSArch::from_str("plan9-x86_64").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_expand() {
         let current = OSArch::from_current();
@@ -1,7 +1,8 @@
 use crate::action::{Dir, Action};
 use crate::provider::{Provider, Options};
 use crate::Phase;
-use anyhow::Error;
+use crate::fs;
+use anyhow::{anyhow, Error};
 use crate::zpkg::payload::{Reader, Writer};
 
 pub struct DirUnix {
@@ -12,14 +13,91 @@ impl DirUnix {
     pub fn new(action: Dir) -> DirUnix {
         DirUnix{ action }
     }
+
+    // Existing directories are adopted, mode and ownership are reapplied
+    fn install(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let target_path = opts.target_path.unwrap();
+        let path = fs::tree_path(&target_path, &self.action.path)?;
+
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) if !meta.is_dir() => return Err(anyhow!("cannot create directory {}, path exists", path.display())),
+            Ok(_) => (),
+            Err(_) => std::fs::create_dir_all(&path)?
+        }
+
+        fs::chmod(&path, self.action.mode)?;
+
+        if fs::is_root() {
+            fs::chown_in(&target_path, &path, &self.action.owner, &self.action.group)?;
+        }
+
+        Ok(Box::new(self.action.clone()))
+    }
+
+    // Directories still holding content are left in place
+    fn remove(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let path = fs::tree_path(opts.target_path.unwrap().as_path(), &self.action.path)?;
+
+        if let Ok(meta) = std::fs::symlink_metadata(&path) {
+            if meta.is_dir() && std::fs::read_dir(&path)?.next().is_none() {
+                std::fs::remove_dir(&path)?;
+            }
+        }
+
+        Ok(Box::new(self.action.clone()))
+    }
 }
 
 impl Provider for DirUnix {
     fn realize(&self, opts: Options, phase: Phase, payload_reader: Option<&Reader>, payload_writer: Option<&mut Writer>) -> Result<Box<dyn Action>, Error> {
         match phase {
-            _ => {}
+            Phase::Install => self.install(opts),
+            Phase::Remove => self.remove(opts),
+            _ => Ok(Box::new(self.action.clone()))
         }
+    }
+}
 
-        Ok(Box::new(self.action.clone()))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_install() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestdir");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("etc"))?;
+        std::fs::write(root.join("etc/zps"), "")?;
+
+        let mut opts = Options::new();
+        opts.target_path = Some(root.clone());
+
+        let dir = Dir {
+            path: "var/lib/zps".to_string(),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mode: 0o750,
+        };
+
+        DirUnix::new(dir.clone()).realize(opts.clone(), Phase::Install, None, None)?;
+        assert_eq!(std::fs::metadata(root.join("var/lib/zps"))?.permissions().mode() & 0o7777, 0o750);
+
+        let mut file = dir.clone();
+        file.path = "etc/zps".to_string();
+        assert!(DirUnix::new(file).realize(opts.clone(), Phase::Install, None, None).is_err());
+
+        let mut parent = dir.clone();
+        parent.path = "var/lib".to_string();
+        DirUnix::new(parent.clone()).realize(opts.clone(), Phase::Remove, None, None)?;
+        assert!(root.join("var/lib").exists());
+
+        DirUnix::new(dir).realize(opts.clone(), Phase::Remove, None, None)?;
+        DirUnix::new(parent).realize(opts, Phase::Remove, None, None)?;
+        assert!(!root.join("var/lib").exists());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
     }
-}
\ No newline at end of file
+}
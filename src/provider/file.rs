@@ -1,7 +1,8 @@
 use crate::action::{File, Action};
 use crate::provider::{Provider, Options};
 use crate::Phase;
-use anyhow::Error;
+use crate::fs;
+use anyhow::{anyhow, Error};
 use crate::zpkg::payload::{Reader, Writer};
 
 pub struct FileUnix {
@@ -24,13 +25,124 @@ impl FileUnix {
 
         Ok(Box::new(action))
     }
+
+    // Written beside the destination then renamed over it, so the path never holds a partial file
+    fn install(&self, opts: Options, payload_reader: &Reader) -> Result<Box<dyn Action>, Error> {
+        let target_path = opts.target_path.unwrap();
+        let path = fs::tree_path(&target_path, &self.action.path)?;
+
+        let tmp_path = path.with_file_name(format!(".{}.zpstmp", path.file_name().unwrap().to_str().unwrap()));
+
+        payload_reader.get_to_path(&self.action, &tmp_path)?;
+
+        let result = fs::chmod(&tmp_path, self.action.mode)
+            .and_then(|_| match fs::is_root() {
+                true => fs::chown_in(&target_path, &tmp_path, &self.action.owner, &self.action.group),
+                false => Ok(())
+            })
+            .and_then(|_| std::fs::rename(&tmp_path, &path).map_err(Error::from));
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+
+        result?;
+
+        Ok(Box::new(self.action.clone()))
+    }
+
+    // The returned action carries the digest found on disk, so callers can detect local changes
+    fn remove(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let path = fs::tree_path(opts.target_path.unwrap().as_path(), &self.action.path)?;
+        let mut action = self.action.clone();
+
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) if meta.is_file() => {
+                action.digest = fs::digest(&path)?;
+                std::fs::remove_file(&path)?;
+            },
+            Ok(_) => return Err(anyhow!("cannot remove file {}, path is not a file", path.display())),
+            Err(_) => ()
+        }
+
+        Ok(Box::new(action))
+    }
 }
 
 impl Provider for FileUnix {
     fn realize(&self, opts: Options, phase: Phase, payload_reader: Option<&Reader>, payload_writer: Option<&mut Writer>) -> Result<Box<dyn Action>, Error> {
         match phase {
             Phase::Package => self.package(opts, payload_writer.unwrap()),
+            Phase::Install => match payload_reader {
+                Some(payload_reader) => self.install(opts, payload_reader),
+                None => Err(anyhow!("payload reader required to install {}", self.action.path))
+            },
+            Phase::Remove => self.remove(opts),
             _ =>  Ok(Box::new(self.action.clone()))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zpkg::{CompType, HashMethod};
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_install() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestfile");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("proto/etc"))?;
+        std::fs::create_dir_all(root.join("tree/etc"))?;
+        std::fs::write(root.join("proto/etc/zps.conf"), "nachos = true\n")?;
+        std::fs::write(root.join("tree/etc/zps.conf"), "stale\n")?;
+
+        let file = File {
+            path: "etc/zps.conf".to_string(),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mode: 0o640,
+            digest: "".to_string(),
+            offset: 0,
+            csize: 0,
+            size: 0,
+        };
+
+        let mut opts = Options::new();
+        opts.target_path = Some(root.join("proto"));
+
+        let mut writer = Writer::new(CompType::ZSTD, HashMethod::SHA3_256, &root)?;
+        let packaged = FileUnix::new(file).realize(opts.clone(), Phase::Package, None, Some(&mut writer))?;
+        let packaged = packaged.as_any().downcast_ref::<File>().unwrap().clone();
+
+        let reader = Reader::new(CompType::ZSTD, HashMethod::SHA3_256, writer.file_path(), 0);
+        opts.target_path = Some(root.join("tree"));
+
+        FileUnix::new(packaged.clone()).realize(opts.clone(), Phase::Install, Some(&reader), None)?;
+
+        assert_eq!(std::fs::read(root.join("tree/etc/zps.conf"))?, b"nachos = true\n");
+        assert_eq!(std::fs::metadata(root.join("tree/etc/zps.conf"))?.permissions().mode() & 0o7777, 0o640);
+        assert!(!root.join("tree/etc/.zps.conf.zpstmp").exists());
+
+        // A corrupt payload leaves the installed file untouched
+        let mut corrupt = packaged.clone();
+        corrupt.size += 1;
+        assert!(FileUnix::new(corrupt).realize(opts.clone(), Phase::Install, Some(&reader), None).is_err());
+        assert_eq!(std::fs::read(root.join("tree/etc/zps.conf"))?, b"nachos = true\n");
+        assert!(!root.join("tree/etc/.zps.conf.zpstmp").exists());
+
+        assert!(FileUnix::new(packaged.clone()).realize(opts.clone(), Phase::Install, None, None).is_err());
+
+        let removed = FileUnix::new(packaged.clone()).realize(opts.clone(), Phase::Remove, None, None)?;
+        assert_eq!(removed.as_any().downcast_ref::<File>().unwrap().digest, packaged.digest);
+        assert!(!root.join("tree/etc/zps.conf").exists());
+
+        // Removing a file which is already gone is not an error
+        FileUnix::new(packaged).realize(opts, Phase::Remove, None, None)?;
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
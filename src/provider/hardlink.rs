@@ -0,0 +1,91 @@
+use crate::action::{HardLink, Action};
+use crate::provider::{Provider, Options};
+use crate::Phase;
+use crate::fs;
+use anyhow::{anyhow, Error};
+use crate::zpkg::payload::{Reader, Writer};
+
+pub struct HardLinkUnix {
+    pub action: HardLink
+}
+
+impl HardLinkUnix {
+    pub fn new(action: HardLink) -> Self {
+        Self { action }
+    }
+
+    // The target file must already be installed, links are realized after files
+    fn install(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let target_path = opts.target_path.unwrap();
+        let path = fs::tree_path(&target_path, &self.action.path)?;
+        let target = fs::tree_path(&target_path, &self.action.target)?;
+
+        if let Ok(meta) = std::fs::symlink_metadata(&path) {
+            if meta.is_dir() {
+                return Err(anyhow!("cannot create hardlink {}, path is a directory", path.display()));
+            }
+
+            std::fs::remove_file(&path)?;
+        }
+
+        std::fs::hard_link(&target, &path)?;
+
+        Ok(Box::new(self.action.clone()))
+    }
+
+    fn remove(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let path = fs::tree_path(opts.target_path.unwrap().as_path(), &self.action.path)?;
+
+        if std::fs::symlink_metadata(&path).map(|m| m.is_file()).unwrap_or(false) {
+            std::fs::remove_file(&path)?;
+        }
+
+        Ok(Box::new(self.action.clone()))
+    }
+}
+
+impl Provider for HardLinkUnix {
+    fn realize(&self, opts: Options, phase: Phase, payload_reader: Option<&Reader>, payload_writer: Option<&mut Writer>) -> Result<Box<dyn Action>, Error> {
+        match phase {
+            Phase::Install => self.install(opts),
+            Phase::Remove => self.remove(opts),
+            _ => Ok(Box::new(self.action.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn test_install() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstesthardlink");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("bin"))?;
+        std::fs::write(root.join("bin/zps"), "zps")?;
+        std::fs::write(root.join("bin/zpkg"), "stale")?;
+
+        let mut opts = Options::new();
+        opts.target_path = Some(root.clone());
+
+        let link = HardLink {
+            path: "bin/zpkg".to_string(),
+            target: "bin/zps".to_string(),
+        };
+
+        HardLinkUnix::new(link.clone()).realize(opts.clone(), Phase::Install, None, None)?;
+
+        assert_eq!(std::fs::metadata(root.join("bin/zpkg"))?.ino(), std::fs::metadata(root.join("bin/zps"))?.ino());
+        assert_eq!(std::fs::read(root.join("bin/zpkg"))?, b"zps");
+
+        HardLinkUnix::new(link).realize(opts, Phase::Remove, None, None)?;
+        assert!(!root.join("bin/zpkg").exists());
+        assert!(root.join("bin/zps").exists());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -1,14 +1,21 @@
 mod dir;
 mod file;
+mod hardlink;
+mod requirement;
+mod symlink;
 mod zpkg;
 
 use anyhow::Error;
+use std::collections::HashSet;
 use std::env;
 use std::path::PathBuf;
 use crate::Phase;
-use crate::action::{Action, ActionType, Dir, File, Zpkg};
+use crate::action::{Action, ActionType, Dir, File, HardLink, Manifest, Requirement, SymLink, Zpkg};
 use dir::*;
 use file::*;
+use hardlink::*;
+use requirement::*;
+use symlink::*;
 use zpkg::*;
 use crate::zpkg::payload::{Reader, Writer};
 
@@ -40,6 +47,92 @@ pub fn provider_for(action: Box<dyn Action>) -> Box<dyn Provider> {
     match action.type_name() {
         ActionType::Dir => Box::new(DirUnix::new(action.as_any().downcast_ref::<Dir>().unwrap().clone())),
         ActionType::File => Box::new(FileUnix::new(action.as_any().downcast_ref::<File>().unwrap().clone())),
+        ActionType::SymLink => Box::new(SymLinkUnix::new(action.as_any().downcast_ref::<SymLink>().unwrap().clone())),
+        ActionType::HardLink => Box::new(HardLinkUnix::new(action.as_any().downcast_ref::<HardLink>().unwrap().clone())),
+        ActionType::Requirement => Box::new(RequirementDefault::new(action.as_any().downcast_ref::<Requirement>().unwrap().clone())),
         ActionType::Zpkg => Box::new(ZpkgDefault::new(action.as_any().downcast_ref::<Zpkg>().unwrap().clone()))
     }
-}
\ No newline at end of file
+}
+
+// Removes what a manifest installed, directories in shared are owned by other packages and kept.
+// Files modified since install are removed all the same, their paths are returned for reporting.
+pub fn remove(manifest: &Manifest, opts: Options, shared: &HashSet<String>) -> Result<Vec<String>, Error> {
+    let mut modified: Vec<String> = Vec::new();
+
+    // Links first, they may point at files about to go
+    for hardlink in manifest.hardlinks.iter() {
+        HardLinkUnix::new(hardlink.clone()).realize(opts.clone(), Phase::Remove, None, None)?;
+    }
+
+    for symlink in manifest.symlinks.iter() {
+        SymLinkUnix::new(symlink.clone()).realize(opts.clone(), Phase::Remove, None, None)?;
+    }
+
+    for file in manifest.files.iter() {
+        let removed = FileUnix::new(file.clone()).realize(opts.clone(), Phase::Remove, None, None)?;
+
+        if removed.as_any().downcast_ref::<File>().unwrap().digest != file.digest {
+            modified.push(file.path.clone());
+        }
+    }
+
+    // Children sort after their parents, so reverse order is deepest first
+    let mut dirs = manifest.dirs.clone();
+    dirs.sort_by(|a, b| b.path.cmp(&a.path));
+
+    for dir in dirs.into_iter().filter(|d| !shared.contains(&d.path)) {
+        DirUnix::new(dir).realize(opts.clone(), Phase::Remove, None, None)?;
+    }
+
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zpkg::tests::{build_zpkg, test_root};
+    use crate::zpkg::reader::Reader as ZpkgReader;
+
+    #[test]
+    fn test_remove() -> Result<(), Error> {
+        let root = test_root("zpstestremove")?;
+        std::fs::create_dir_all(root.join("proto/etc/zps"))?;
+        std::fs::create_dir_all(root.join("proto/usr/bin"))?;
+        std::fs::write(root.join("proto/etc/zps/zps.conf"), "nachos = true\n")?;
+        std::fs::write(root.join("proto/usr/bin/zps"), "#!/bin/sh\n")?;
+        std::os::unix::fs::symlink("zps", root.join("proto/usr/bin/zpkg"))?;
+
+        let zpkg = build_zpkg(&root, "remove", "1.0.0", &root)?;
+
+        let mut reader = ZpkgReader::new(&zpkg, &root);
+        reader.read()?;
+        let payload = reader.payload()?;
+        let manifest = reader.manifest.take().unwrap();
+
+        let mut opts = Options::new();
+        opts.target_path = Some(root.join("tree"));
+        std::fs::create_dir_all(root.join("tree"))?;
+
+        for action in manifest.actions() {
+            provider_for(action).realize(opts.clone(), Phase::Install, Some(&payload), None)?;
+        }
+
+        std::fs::write(root.join("tree/etc/zps/zps.conf"), "nachos = false\n")?;
+        std::fs::write(root.join("tree/usr/bin/other"), "")?;
+
+        let mut shared = HashSet::new();
+        shared.insert("etc".to_string());
+
+        let modified = remove(&manifest, opts, &shared)?;
+
+        assert_eq!(modified, vec!["etc/zps/zps.conf".to_string()]);
+        assert!(!root.join("tree/etc/zps").exists());
+        assert!(root.join("tree/etc").exists());
+        assert!(std::fs::symlink_metadata(root.join("tree/usr/bin/zpkg")).is_err());
+        assert!(!root.join("tree/usr/bin/zps").exists());
+        assert!(root.join("tree/usr/bin/other").exists());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::action::{Action, Requirement};
+use crate::provider::{Provider, Options};
+use crate::Phase;
+use anyhow::Error;
+use crate::zpkg::payload::{Reader, Writer};
+
+// Requirements are metadata for the solver, there is nothing to realize
+pub struct RequirementDefault {
+    pub action: Requirement
+}
+
+impl RequirementDefault {
+    pub fn new(action: Requirement) -> RequirementDefault {
+        RequirementDefault{ action }
+    }
+}
+
+impl Provider for RequirementDefault {
+    fn realize(&self, opts: Options, phase: Phase, payload_reader: Option<&Reader>, payload_writer: Option<&mut Writer>) -> Result<Box<dyn Action>, Error> {
+        Ok(Box::new(self.action.clone()))
+    }
+}
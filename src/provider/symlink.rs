@@ -0,0 +1,103 @@
+use crate::action::{SymLink, Action};
+use crate::provider::{Provider, Options};
+use crate::Phase;
+use crate::fs;
+use anyhow::{anyhow, Error};
+use crate::zpkg::payload::{Reader, Writer};
+
+pub struct SymLinkUnix {
+    pub action: SymLink
+}
+
+impl SymLinkUnix {
+    pub fn new(action: SymLink) -> Self {
+        Self { action }
+    }
+
+    // Replaces an existing link in place, anything else at the path is left alone
+    fn install(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let target_path = opts.target_path.unwrap();
+        let path = fs::tree_path(&target_path, &self.action.path)?;
+
+        if let Ok(meta) = std::fs::symlink_metadata(&path) {
+            if !meta.file_type().is_symlink() {
+                return Err(anyhow!("cannot create symlink {}, path exists", path.display()));
+            }
+
+            std::fs::remove_file(&path)?;
+        }
+
+        std::os::unix::fs::symlink(&self.action.target, &path)?;
+
+        if fs::is_root() {
+            fs::chown_in(&target_path, &path, &self.action.owner, &self.action.group)?;
+        }
+
+        Ok(Box::new(self.action.clone()))
+    }
+
+    fn remove(&self, opts: Options) -> Result<Box<dyn Action>, Error> {
+        let path = fs::tree_path(opts.target_path.unwrap().as_path(), &self.action.path)?;
+
+        if std::fs::symlink_metadata(&path).map(|m| m.file_type().is_symlink()).unwrap_or(false) {
+            std::fs::remove_file(&path)?;
+        }
+
+        Ok(Box::new(self.action.clone()))
+    }
+}
+
+impl Provider for SymLinkUnix {
+    fn realize(&self, opts: Options, phase: Phase, payload_reader: Option<&Reader>, payload_writer: Option<&mut Writer>) -> Result<Box<dyn Action>, Error> {
+        match phase {
+            Phase::Install => self.install(opts),
+            Phase::Remove => self.remove(opts),
+            _ => Ok(Box::new(self.action.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_install() -> Result<(), Error> {
+        let root = env::temp_dir().join("zpstestsymlink");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("lib"))?;
+        std::fs::write(root.join("lib/libfoo.so.1"), "foo")?;
+
+        let mut opts = Options::new();
+        opts.target_path = Some(root.clone());
+
+        let link = SymLink {
+            path: "lib/libfoo.so".to_string(),
+            target: "libfoo.so.1".to_string(),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+        };
+
+        // Installing twice replaces the existing link
+        SymLinkUnix::new(link.clone()).realize(opts.clone(), Phase::Install, None, None)?;
+        SymLinkUnix::new(link.clone()).realize(opts.clone(), Phase::Install, None, None)?;
+
+        assert_eq!(std::fs::read_link(root.join("lib/libfoo.so"))?.to_str().unwrap(), "libfoo.so.1");
+        assert_eq!(std::fs::read(root.join("lib/libfoo.so"))?, b"foo");
+
+        let mut file = link.clone();
+        file.path = "lib/libfoo.so.1".to_string();
+        assert!(SymLinkUnix::new(file.clone()).realize(opts.clone(), Phase::Install, None, None).is_err());
+
+        SymLinkUnix::new(link).realize(opts.clone(), Phase::Remove, None, None)?;
+        assert!(std::fs::symlink_metadata(root.join("lib/libfoo.so")).is_err());
+
+        // Only links are removed
+        SymLinkUnix::new(file).realize(opts, Phase::Remove, None, None)?;
+        assert!(root.join("lib/libfoo.so.1").exists());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+use url::Url;
+
+use crate::{Package, Repo};
+use crate::config::Config;
+use crate::fetcher::{self, NotFound};
+use crate::index::{Entry, Index, INDEX};
+
+const DEFAULT_RETENTION: i32 = 10;
+const PUBLISH_DIR: &str = "publish";
+
+// Outcome of a publish, by zpkg file name
+pub struct Report {
+    pub published: Vec<String>,
+    // Already in the repository, left as they were
+    pub rejected: Vec<String>,
+    // Beyond the retention count, dropped from the index and removed
+    pub pruned: Vec<String>,
+}
+
+// Adds zpkgs to a repository, rewriting its index once they are in place
+pub struct Publisher {
+    config: Config,
+
+    repo_uri: Option<String>,
+    zpkg_paths: Vec<PathBuf>,
+    work_path: Option<PathBuf>,
+
+    retention: i32,
+}
+
+impl Publisher {
+    pub fn new(config: &Config) -> Publisher {
+        Publisher {
+            config: config.clone(),
+            repo_uri: None,
+            zpkg_paths: Vec::new(),
+            work_path: None,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    // A repository uri or the path of a local repository
+    pub fn repo(&mut self, uri: String) -> &mut Publisher {
+        self.repo_uri = Some(uri);
+        self
+    }
+
+    pub fn zpkg(&mut self, path: String) -> &mut Publisher {
+        self.zpkg_paths.push(PathBuf::from(path));
+        self
+    }
+
+    pub fn work(&mut self, path: String) -> &mut Publisher {
+        self.work_path = Some(PathBuf::from(path));
+        self
+    }
+
+    // Versions of each package kept in the repository
+    pub fn retention(&mut self, count: i32) -> &mut Publisher {
+        self.retention = count;
+        self
+    }
+
+    pub fn publish(&mut self) -> Result<Report, Error> {
+        let uri = match self.repo_uri.as_ref() {
+            Some(uri) => Url::parse(uri).or_else(|_| {
+                Url::from_file_path(env::current_dir()?.join(uri))
+                    .map_err(|_| anyhow!("invalid repository: {}", uri))
+            })?,
+            None => return Err(anyhow!("repository is required")),
+        };
+
+        if self.zpkg_paths.is_empty() {
+            return Err(anyhow!("at least one zpkg is required"));
+        }
+
+        if self.retention < 1 {
+            return Err(anyhow!("retention must be at least 1, got {}", self.retention));
+        }
+
+        let work_path = match self.work_path.as_ref() {
+            Some(path) => path.clone(),
+            None => env::current_dir()?,
+        };
+
+        // Every zpkg is read before anything is uploaded
+        let mut zpkgs: Vec<(PathBuf, Package, Entry)> = Vec::new();
+
+        for path in self.zpkg_paths.iter() {
+            let mut entry = Entry::from_zpkg(path, &work_path)
+                .map_err(|err| anyhow!("{}: {}", path.display(), err))?;
+            let package = entry.package()?;
+
+            // Fetchers find zpkgs by the name the package gives them
+            entry.file_name = package.file_name();
+
+            zpkgs.push((path.clone(), package, entry));
+        }
+
+        let tmp_path = self.config.tmp_path().join(PUBLISH_DIR);
+        let _ = std::fs::remove_dir_all(&tmp_path);
+        std::fs::create_dir_all(&tmp_path)?;
+
+        let result = self.update(&uri, zpkgs, &tmp_path.join(INDEX));
+        let _ = std::fs::remove_dir_all(&tmp_path);
+
+        result.map_err(|err| anyhow!("failed to publish to {}: {}", uri, err))
+    }
+
+    fn update(&self, uri: &Url, zpkgs: Vec<(PathBuf, Package, Entry)>, index_path: &Path) -> Result<Report, Error> {
+        let publisher = fetcher::publisher_for(uri, &self.config)?;
+
+        // A missing index is a new repository, anything else could lose what's published
+        let index = match fetcher::fetcher_for(uri, &self.config)?.fetch(INDEX, index_path) {
+            Ok(()) => Index::read(index_path)?,
+            Err(err) if err.is::<NotFound>() => Index::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut repo = Repo::new(uri.clone(), 0, true);
+        let mut entries: HashMap<String, Entry> = HashMap::new();
+
+        for entry in index.packages.into_iter() {
+            let package = entry.package()
+                .map_err(|err| anyhow!("invalid index entry {}: {}", entry.file_name, err))?;
+
+            entries.insert(package.id(), entry);
+            repo.load(vec![package]);
+        }
+
+        // Added one at a time so a zpkg given twice is only rejected the second time
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+
+        for (path, package, entry) in zpkgs.into_iter() {
+            match repo.add(std::slice::from_ref(&package)).is_empty() {
+                true => accepted.push((path, package, entry)),
+                false => rejected.push(entry.file_name),
+            }
+        }
+
+        let pruned = repo.prune(self.retention).unwrap_or_default();
+        let pruned_ids: HashSet<String> = pruned.iter().map(|p| p.id()).collect();
+
+        let mut published = Vec::new();
+
+        // Packages go up before the index which references them
+        for (path, package, entry) in accepted.into_iter() {
+            if !pruned_ids.contains(&package.id()) {
+                publisher.publish(&path, &entry.file_name)?;
+                published.push(entry.file_name.clone());
+            }
+
+            entries.insert(package.id(), entry);
+        }
+
+        let mut index = Index::new();
+        index.packages = repo.contents().iter()
+            .filter_map(|package| entries.get(&package.id()).cloned())
+            .collect();
+
+        index.write(index_path)?;
+        publisher.publish(index_path, INDEX)?;
+
+        // Only once nothing references them
+        let mut removed = Vec::new();
+
+        for package in pruned.iter() {
+            let file_name = entries.get(&package.id()).unwrap().file_name.clone();
+
+            publisher.remove(&file_name)?;
+            removed.push(file_name);
+        }
+
+        Ok(Report {
+            published,
+            rejected,
+            pruned: removed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zpkg::tests::{build_zpkg, test_root};
+
+    #[test]
+    fn test_publish() -> Result<(), Error> {
+        let root = test_root("zpstestpublisher")?;
+        let build = |name, version| build_zpkg(&root, name, version, &root.join("build"))
+            .map(|path| path.to_str().unwrap().to_string());
+
+        let nachos = build("nachos", "1.0.0")?;
+        let salsa = build("salsa", "1.0.0")?;
+        let nachos1 = build("nachos", "1.1.0")?;
+        let nachos2 = build("nachos", "1.2.0")?;
+
+        let config = Config::for_tree(&root.join("tree"));
+        let repo = root.join("repo").to_str().unwrap().to_string();
+        let file_name = |path: &String| PathBuf::from(path).file_name().unwrap().to_str().unwrap().to_string();
+
+        let mut publisher = Publisher::new(&config);
+        publisher.work(root.to_str().unwrap().to_string());
+        assert_eq!(publisher.publish().err().unwrap().to_string(), "repository is required");
+
+        publisher.repo(repo.clone());
+        assert_eq!(publisher.publish().err().unwrap().to_string(), "at least one zpkg is required");
+
+        let report = publisher.zpkg(nachos.clone()).zpkg(salsa.clone()).zpkg(nachos.clone()).publish()?;
+        assert_eq!(report.published, vec![file_name(&nachos), file_name(&salsa)]);
+        assert_eq!(report.rejected, vec![file_name(&nachos)]);
+        assert!(report.pruned.is_empty());
+
+        let index = Index::read(&root.join("repo").join(INDEX))?;
+        assert_eq!(index.packages.len(), 2);
+        assert_eq!(index.packages[0].file_name, file_name(&nachos));
+        assert_eq!(std::fs::read(root.join("repo").join(file_name(&nachos)))?, std::fs::read(&nachos)?);
+
+        // The oldest nachos goes, as does the one being published which is already too old
+        let report = Publisher::new(&config)
+            .repo(repo.clone())
+            .work(root.to_str().unwrap().to_string())
+            .retention(1)
+            .zpkg(nachos2.clone())
+            .zpkg(nachos1.clone())
+            .publish()?;
+        assert_eq!(report.published, vec![file_name(&nachos2)]);
+        assert!(report.rejected.is_empty());
+        assert_eq!(report.pruned, vec![file_name(&nachos), file_name(&nachos1)]);
+
+        let index = Index::read(&root.join("repo").join(INDEX))?;
+        let names: Vec<String> = index.packages.iter().map(|e| e.file_name.clone()).collect();
+        assert_eq!(names, vec![file_name(&nachos2), file_name(&salsa)]);
+        assert!(!root.join("repo").join(file_name(&nachos)).exists());
+        assert!(!root.join("repo").join(file_name(&nachos1)).exists());
+        assert!(!config.tmp_path().join(PUBLISH_DIR).exists());
+
+        // A corrupt index is never replaced
+        std::fs::write(root.join("repo").join(INDEX), "salsa")?;
+        let err = Publisher::new(&config)
+            .repo(repo.clone())
+            .work(root.to_str().unwrap().to_string())
+            .zpkg(nachos.clone())
+            .publish()
+            .err().unwrap().to_string();
+        assert!(err.contains("not a repository index"), "{}", err);
+        assert_eq!(std::fs::read(root.join("repo").join(INDEX))?, b"salsa");
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
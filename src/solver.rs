@@ -0,0 +1,697 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fmt::Display;
+
+use anyhow::{anyhow, Error};
+
+use crate::{Operation, OperationMethod, Package, Repo, Request, RequestMethod, Requirement, RequirementMethod};
+use crate::db::State;
+use crate::platform::OSArch;
+
+// Why a requirement could not be met, rejected candidates carry their own derivations
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Derivation {
+    requirement: Requirement,
+    required_by: Option<String>,
+    #[serde(flatten)]
+    cause: Cause,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "cause", rename_all = "snake_case")]
+enum Cause {
+    Removed,
+    Selected { package: String },
+    Unavailable,
+    Rejected { candidates: Vec<Rejection> },
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+enum Rejection {
+    Conflict { package: String, with: String, installed: bool, declared_by: String, conflict: Requirement },
+    Removed { package: String },
+    Selected { package: String, selected: String },
+    Unsatisfiable { package: String, derivation: Box<Derivation> },
+}
+
+impl Derivation {
+    fn new(requirement: Requirement, required_by: Option<&Package>, cause: Cause) -> Derivation {
+        Derivation {
+            requirement,
+            required_by: required_by.map(|p| p.id()),
+            cause,
+        }
+    }
+
+    fn needs(&self) -> String {
+        match self.required_by.as_ref() {
+            Some(id) => format!("{} requires {}", id, self.requirement),
+            None => format!("{} was requested", self.requirement),
+        }
+    }
+
+    // Indented tree of every candidate tried, one line per step
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        self.render(0, &mut lines);
+        lines
+    }
+
+    fn render(&self, depth: usize, lines: &mut Vec<String>) {
+        let indent = |depth: usize| "  ".repeat(depth);
+
+        lines.push(format!("{}{}", indent(depth), self.needs()));
+
+        match &self.cause {
+            Cause::Removed => lines.push(format!("{}{} is being removed", indent(depth + 1), self.requirement.name)),
+            Cause::Selected { package } => lines.push(format!("{}{} is selected", indent(depth + 1), package)),
+            Cause::Unavailable => lines.push(format!("{}no matching package is available", indent(depth + 1))),
+            Cause::Rejected { candidates } => {
+                for rejection in candidates.iter() {
+                    match rejection {
+                        Rejection::Unsatisfiable { package, derivation } => {
+                            // The failure may be further along the branch than the candidate itself
+                            if derivation.required_by.as_ref() == Some(package) {
+                                derivation.render(depth + 1, lines);
+                            } else {
+                                lines.push(format!("{}{} was selected", indent(depth + 1), package));
+                                derivation.render(depth + 2, lines);
+                            }
+                        },
+                        _ => lines.push(format!("{}{}", indent(depth + 1), rejection)),
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, Error> {
+        match serde_json::ser::to_vec(self) {
+            Ok(result) => Ok(result),
+            Err(err) => Err(Error::from(err))
+        }
+    }
+}
+
+// Follows the best candidate down to the first failure
+impl Display for Derivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.cause {
+            Cause::Removed => write!(f, "{}, but {} is being removed", self.needs(), self.requirement.name),
+            Cause::Selected { package } => write!(f, "{}, but {} is selected", self.needs(), package),
+            Cause::Unavailable => write!(f, "{}, but no matching package is available", self.needs()),
+            Cause::Rejected { candidates } => match &candidates[0] {
+                Rejection::Unsatisfiable { derivation, .. } => derivation.fmt(f),
+                rejection => write!(f, "{}, but {}", self.needs(), rejection),
+            },
+        }
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::Conflict { package, with, installed, .. } => {
+                let which = if *installed { "installed" } else { "selected" };
+                write!(f, "{} conflicts with {} which is {}", package, with, which)
+            },
+            Rejection::Removed { package } => write!(f, "{} is being removed", package),
+            Rejection::Selected { package, selected } => write!(f, "{} cannot replace {} which is selected", package, selected),
+            Rejection::Unsatisfiable { derivation, .. } => derivation.fmt(f),
+        }
+    }
+}
+
+// Returned by Solver::solve when the request cannot be met, recover it with downcast_ref
+#[derive(Debug)]
+pub struct Unsatisfiable {
+    pub derivation: Derivation,
+}
+
+impl Display for Unsatisfiable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no solution: {}", self.derivation)
+    }
+}
+
+impl std::error::Error for Unsatisfiable {}
+
+// Packages chosen so far on one branch of the search
+#[derive(Clone)]
+struct Selection {
+    packages: BTreeMap<String, Package>,
+
+    // Names settled on this branch, installed packages may be swapped out until they are
+    decided: HashSet<String>,
+    removed: HashSet<String>,
+}
+
+// Backtracking search from the installed set towards one satisfying every requirement
+pub struct Solver {
+    target: OSArch,
+    installed: Vec<Package>,
+
+    // Available packages along with the priority of the repo they came from
+    candidates: Vec<(u32, Package)>,
+}
+
+impl Solver {
+    pub fn new(target: OSArch, installed: Vec<Package>, repos: &[Repo]) -> Solver {
+        let mut candidates: Vec<(u32, Package)> = Vec::new();
+
+        for repo in repos.iter().filter(|r| r.enabled) {
+            candidates.extend(repo.clone().contents().into_iter().map(|p| (repo.priority, p)));
+        }
+
+        Solver {
+            target,
+            installed,
+            candidates,
+        }
+    }
+
+    pub fn from_state(target: OSArch, state: &mut State, repos: &[Repo]) -> Result<Solver, Error> {
+        let installed = state.pkg_list()?
+            .into_iter()
+            .map(Package::from)
+            .collect::<Result<Vec<Package>, Error>>()?;
+
+        Ok(Self::new(target, installed, repos))
+    }
+
+    // Removals come first, dependents before their dependencies, then installs with dependencies first
+    pub fn solve(&self, request: &Request) -> Result<Vec<Operation>, Error> {
+        let mut selection = Selection {
+            packages: self.installed.iter().map(|p| (p.name.clone(), p.clone())).collect(),
+            decided: HashSet::new(),
+            removed: HashSet::new(),
+        };
+
+        let mut roots: Vec<Requirement> = Vec::new();
+
+        for job in request.jobs.iter() {
+            match job.method {
+                RequestMethod::Install => roots.push(job.req.clone()),
+                RequestMethod::Remove => {
+                    if selection.packages.remove(&job.req.name).is_none() {
+                        return Err(anyhow!("cannot remove {}, package is not installed", job.req.name));
+                    }
+
+                    selection.removed.insert(job.req.name.clone());
+                }
+            }
+        }
+
+        let selection = self.search(selection, &roots).map_err(|derivation| Unsatisfiable { derivation })?;
+
+        let installed: HashMap<&str, &Package> = self.installed.iter().map(|p| (p.name.as_str(), p)).collect();
+
+        let removes: Vec<Package> = self.installed.iter()
+            .filter(|p| !selection.packages.contains_key(&p.name))
+            .cloned()
+            .collect();
+
+        let installs: Vec<Package> = selection.packages.values()
+            .filter(|p| installed.get(p.name.as_str()).map_or(true, |i| i.id() != p.id()))
+            .cloned()
+            .collect();
+
+        let mut operations: Vec<Operation> = Vec::new();
+
+        for package in Self::order(removes).into_iter().rev() {
+            operations.push(Operation::new(OperationMethod::Remove, package));
+        }
+
+        for package in Self::order(installs) {
+            operations.push(Operation::new(OperationMethod::Install, package));
+        }
+
+        Ok(operations)
+    }
+
+    fn search(&self, selection: Selection, roots: &[Requirement]) -> Result<Selection, Derivation> {
+        let (requirer, req) = match self.unsatisfied(&selection, roots) {
+            Some(unsatisfied) => unsatisfied,
+            None => return Ok(selection),
+        };
+
+        if selection.removed.contains(&req.name) {
+            return Err(Derivation::new(req, requirer.as_ref(), Cause::Removed));
+        }
+
+        if selection.decided.contains(&req.name) {
+            let package = selection.packages[&req.name].id();
+            return Err(Derivation::new(req, requirer.as_ref(), Cause::Selected { package }));
+        }
+
+        let candidates = self.candidates(&req);
+        if candidates.is_empty() {
+            return Err(Derivation::new(req, requirer.as_ref(), Cause::Unavailable));
+        }
+
+        // Best candidate first, its failure is the most useful one to summarize
+        let mut rejections: Vec<Rejection> = Vec::new();
+
+        for candidate in candidates {
+            // Only providers of a virtual name get this far while removed or settled
+            if selection.removed.contains(&candidate.name) {
+                rejections.push(Rejection::Removed { package: candidate.id() });
+                continue;
+            }
+
+            if selection.decided.contains(&candidate.name) {
+                let selected = selection.packages[&candidate.name].id();
+                rejections.push(Rejection::Selected { package: candidate.id(), selected });
+                continue;
+            }
+
+            if let Some(conflict) = self.conflict(&selection, &candidate) {
+                rejections.push(conflict);
+                continue;
+            }
+
+            let package = candidate.id();
+
+            let mut next = selection.clone();
+            next.decided.insert(candidate.name.clone());
+            next.packages.insert(candidate.name.clone(), candidate);
+
+            match self.search(next, roots) {
+                Ok(solved) => return Ok(solved),
+                Err(derivation) => rejections.push(Rejection::Unsatisfiable { package, derivation: Box::new(derivation) }),
+            }
+        }
+
+        Err(Derivation::new(req, requirer.as_ref(), Cause::Rejected { candidates: rejections }))
+    }
+
+    // Requested requirements first, then dependencies of selected packages in name order
+    fn unsatisfied(&self, selection: &Selection, roots: &[Requirement]) -> Option<(Option<Package>, Requirement)> {
+        // Any selected package may satisfy a requirement, by name or by what it provides
+        let satisfied = |req: &Requirement| {
+            selection.packages.values().any(|p| p.satisfies(req.clone()))
+        };
+
+        if let Some(req) = roots.iter().find(|r| !satisfied(r)) {
+            return Some((None, req.clone()));
+        }
+
+        for package in selection.packages.values() {
+            for req in package.requirements.iter().filter(|r| r.method == RequirementMethod::Depends) {
+                if !satisfied(req) {
+                    return Some((Some(package.clone()), (**req).clone()));
+                }
+            }
+        }
+
+        None
+    }
+
+    // Available packages for the target platform, best first. A package of the required name comes
+    // before its providers, which rank by their best repo priority and then their own priority.
+    fn candidates(&self, req: &Requirement) -> Vec<Package> {
+        let platforms = self.target.expand();
+
+        // Installed packages no longer in a repo rank last among providers
+        let mut candidates: Vec<(u32, Package)> = self.installed.iter()
+            .map(|p| (u32::MAX, p.clone()))
+            .chain(self.candidates.iter().cloned())
+            .filter(|(_, p)| p.satisfies(req.clone()))
+            .filter(|(_, p)| platforms.contains(&OSArch::new(p.os, p.arch)))
+            .collect();
+
+        let mut ranks: HashMap<String, (bool, u32, i32)> = HashMap::new();
+
+        for (priority, package) in candidates.iter() {
+            let rank = (package.name != req.name, *priority, package.priority);
+            let best = ranks.entry(package.name.clone()).or_insert(rank);
+
+            if rank < *best {
+                *best = rank;
+            }
+        }
+
+        candidates.sort_by(|(_, a), (_, b)| {
+            ranks[&a.name].cmp(&ranks[&b.name])
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| b.cmp(a))
+        });
+
+        let mut seen: HashSet<String> = HashSet::new();
+
+        candidates.into_iter()
+            .map(|(_, p)| p)
+            .filter(|p| seen.insert(p.id()))
+            .collect()
+    }
+
+    fn conflict(&self, selection: &Selection, candidate: &Package) -> Option<Rejection> {
+        let conflicts = |package: &Package, other: &Package| {
+            package.requirements.iter()
+                .find(|r| r.method == RequirementMethod::Conflicts && other.satisfies((***r).clone()))
+                .map(|r| (package.id(), (**r).clone()))
+        };
+
+        for other in selection.packages.values().filter(|p| p.name != candidate.name) {
+            if let Some((declared_by, conflict)) = conflicts(candidate, other).or_else(|| conflicts(other, candidate)) {
+                return Some(Rejection::Conflict {
+                    package: candidate.id(),
+                    with: other.id(),
+                    installed: !selection.decided.contains(&other.name),
+                    declared_by,
+                    conflict,
+                });
+            }
+        }
+
+        None
+    }
+
+    // Dependencies before dependents, ties broken by name
+    fn order(packages: Vec<Package>) -> Vec<Package> {
+        let by_name: BTreeMap<String, Package> = packages.into_iter().map(|p| (p.name.clone(), p)).collect();
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut ordered: Vec<Package> = Vec::new();
+
+        fn visit(name: &str, by_name: &BTreeMap<String, Package>, visited: &mut HashSet<String>, ordered: &mut Vec<Package>) {
+            if !visited.insert(name.to_string()) {
+                return;
+            }
+
+            let package = &by_name[name];
+
+            // Providers of a virtual name count as dependencies too
+            for req in package.requirements.iter().filter(|r| r.method == RequirementMethod::Depends) {
+                let providers: Vec<String> = by_name.values()
+                    .filter(|p| p.name != name && p.satisfies((**req).clone()))
+                    .map(|p| p.name.clone())
+                    .collect();
+
+                for provider in providers {
+                    visit(&provider, by_name, visited, ordered);
+                }
+            }
+
+            ordered.push(package.clone());
+        }
+
+        for name in by_name.keys() {
+            visit(name, &by_name, &mut visited, &mut ordered);
+        }
+
+        ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Comparator, Version};
+    use crate::console::UI;
+    use crate::platform::{Arch, OS};
+    use url::Url;
+
+    fn package(name: &str, version: &str, requirements: Vec<Requirement>) -> Package {
+        let mut package = Package::new(
+            name.to_string(),
+            Version::from(format!("{}:20200415T194203Z", version)).unwrap(),
+            "zps.io".to_string(),
+            OS::Linux,
+            Arch::X8664,
+            "test".to_string(),
+            "test".to_string(),
+        );
+
+        package.requirements = requirements.into_iter().map(Box::new).collect();
+        package
+    }
+
+    fn req(name: &str, method: RequirementMethod, comparator: Comparator, version: Option<&str>) -> Requirement {
+        Requirement::new(
+            name.to_string(),
+            method,
+            comparator,
+            version.map(|v| Version::from(format!("{}:20200101T000000Z", v)).unwrap()),
+        )
+    }
+
+    fn depends(name: &str) -> Requirement {
+        req(name, RequirementMethod::Depends, Comparator::ANY, None)
+    }
+
+    fn solver(installed: Vec<Package>, available: Vec<Package>) -> Solver {
+        let mut repo = Repo::new(Url::parse("file:///tmp/zpstestsolver").unwrap(), 10, true);
+        repo.load(available);
+
+        Solver::new(OSArch::new(OS::Linux, Arch::X8664), installed, &[repo])
+    }
+
+    fn plan(operations: &[Operation]) -> Vec<String> {
+        operations.iter().map(|o| format!("{} {}", o.method, o.package.id())).collect()
+    }
+
+    #[test]
+    fn test_solve_install() -> Result<(), Error> {
+        let solver = solver(vec![], vec![
+            package("nachos", "1.0.0", vec![depends("salsa")]),
+            package("salsa", "1.0.0", vec![]),
+            package("salsa", "2.0.0", vec![depends("chips")]),
+            package("chips", "1.0.0", vec![]),
+        ]);
+
+        let mut request = Request::new();
+        request.install(depends("nachos"));
+
+        assert_eq!(plan(&solver.solve(&request)?), vec![
+            "install chips@1.0.0:20200415T194203Z",
+            "install salsa@2.0.0:20200415T194203Z",
+            "install nachos@1.0.0:20200415T194203Z",
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_backtrack() -> Result<(), Error> {
+        let solver = solver(
+            vec![package("queso", "1.0.0", vec![])],
+            vec![
+                package("nachos", "2.0.0", vec![req("salsa", RequirementMethod::Depends, Comparator::GTE, Some("2.0.0"))]),
+                package("nachos", "1.0.0", vec![depends("salsa")]),
+                package("salsa", "2.0.0", vec![req("queso", RequirementMethod::Conflicts, Comparator::ANY, None)]),
+                package("salsa", "1.0.0", vec![]),
+            ],
+        );
+
+        let mut request = Request::new();
+        request.install(depends("nachos"));
+
+        assert_eq!(plan(&solver.solve(&request)?), vec![
+            "install salsa@1.0.0:20200415T194203Z",
+            "install nachos@1.0.0:20200415T194203Z",
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_upgrade() -> Result<(), Error> {
+        let solver = solver(
+            vec![package("salsa", "1.0.0", vec![])],
+            vec![
+                package("nachos", "1.0.0", vec![req("salsa", RequirementMethod::Depends, Comparator::GTE, Some("2.0.0"))]),
+                package("salsa", "2.0.0", vec![]),
+            ],
+        );
+
+        let mut request = Request::new();
+        request.install(depends("nachos"));
+
+        assert_eq!(plan(&solver.solve(&request)?), vec![
+            "install salsa@2.0.0:20200415T194203Z",
+            "install nachos@1.0.0:20200415T194203Z",
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_remove() -> Result<(), Error> {
+        let solver = solver(
+            vec![package("nachos", "1.0.0", vec![depends("salsa")]), package("salsa", "1.0.0", vec![])],
+            vec![],
+        );
+
+        let mut request = Request::new();
+        request.remove(depends("nachos"));
+        assert_eq!(plan(&solver.solve(&request)?), vec!["remove nachos@1.0.0:20200415T194203Z"]);
+
+        let mut request = Request::new();
+        request.remove(depends("salsa"));
+        assert_eq!(
+            solver.solve(&request).err().unwrap().to_string(),
+            "no solution: nachos@1.0.0:20200415T194203Z requires salsa, but salsa is being removed"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_unsatisfiable() {
+        let solver = solver(
+            vec![package("queso", "1.0.0", vec![])],
+            vec![
+                package("nachos", "1.0.0", vec![req("salsa", RequirementMethod::Depends, Comparator::GTE, Some("3.0.0"))]),
+                package("salsa", "3.0.0", vec![req("queso", RequirementMethod::Conflicts, Comparator::ANY, None)]),
+            ],
+        );
+
+        let mut request = Request::new();
+        request.install(depends("nachos"));
+        assert_eq!(
+            solver.solve(&request).err().unwrap().to_string(),
+            "no solution: nachos@1.0.0:20200415T194203Z requires salsa>=3.0.0, \
+             but salsa@3.0.0:20200415T194203Z conflicts with queso@1.0.0:20200415T194203Z which is installed"
+        );
+
+        let mut request = Request::new();
+        request.install(depends("guacamole"));
+        assert_eq!(
+            solver.solve(&request).err().unwrap().to_string(),
+            "no solution: guacamole was requested, but no matching package is available"
+        );
+    }
+
+    #[test]
+    fn test_solve_derivation() -> Result<(), Error> {
+        let solver = solver(
+            vec![package("queso", "1.0.0", vec![])],
+            vec![
+                package("nachos", "2.0.0", vec![req("salsa", RequirementMethod::Depends, Comparator::GTE, Some("3.0.0"))]),
+                package("nachos", "1.0.0", vec![depends("chips")]),
+                package("salsa", "3.0.0", vec![req("queso", RequirementMethod::Conflicts, Comparator::ANY, None)]),
+            ],
+        );
+
+        let mut request = Request::new();
+        request.install(depends("nachos"));
+
+        let err = solver.solve(&request).err().unwrap();
+        let derivation = &err.downcast_ref::<Unsatisfiable>().unwrap().derivation;
+
+        assert_eq!(
+            err.to_string(),
+            "no solution: nachos@2.0.0:20200415T194203Z requires salsa>=3.0.0, \
+             but salsa@3.0.0:20200415T194203Z conflicts with queso@1.0.0:20200415T194203Z which is installed"
+        );
+
+        assert_eq!(derivation.lines(), vec![
+            "nachos was requested",
+            "  nachos@2.0.0:20200415T194203Z requires salsa>=3.0.0",
+            "    salsa@3.0.0:20200415T194203Z conflicts with queso@1.0.0:20200415T194203Z which is installed",
+            "  nachos@1.0.0:20200415T194203Z requires chips",
+            "    no matching package is available",
+        ]);
+
+        // As the console shows it
+        assert_eq!(UI::error_lines(&err), vec![
+            format!("Error: {}", err),
+            "  nachos was requested".to_string(),
+            "    nachos@2.0.0:20200415T194203Z requires salsa>=3.0.0".to_string(),
+            "      salsa@3.0.0:20200415T194203Z conflicts with queso@1.0.0:20200415T194203Z which is installed".to_string(),
+            "    nachos@1.0.0:20200415T194203Z requires chips".to_string(),
+            "      no matching package is available".to_string(),
+        ]);
+
+        let json: serde_json::Value = serde_json::from_slice(&derivation.to_json()?)?;
+        assert_eq!(json["cause"], "rejected");
+        assert_eq!(json["requirement"]["constraint"], "nachos");
+        assert_eq!(json["required_by"], serde_json::Value::Null);
+
+        let salsa = &json["candidates"][0]["derivation"];
+        assert_eq!(json["candidates"][0]["reason"], "unsatisfiable");
+        assert_eq!(salsa["required_by"], "nachos@2.0.0:20200415T194203Z");
+        assert_eq!(salsa["candidates"][0]["reason"], "conflict");
+        assert_eq!(salsa["candidates"][0]["declared_by"], "salsa@3.0.0:20200415T194203Z");
+        assert_eq!(salsa["candidates"][0]["conflict"]["method"], "conflicts");
+        assert_eq!(salsa["candidates"][0]["installed"], true);
+
+        assert_eq!(json["candidates"][1]["derivation"]["cause"], "unavailable");
+
+        Ok(())
+    }
+
+    fn provides(name: &str, version: Option<&str>) -> Requirement {
+        let comparator = if version.is_some() { Comparator::EQ } else { Comparator::ANY };
+        req(name, RequirementMethod::Provides, comparator, version)
+    }
+
+    #[test]
+    fn test_solve_provides() -> Result<(), Error> {
+        let mut sendmail = package("sendmail", "8.15.0", vec![provides("mail-transport-agent", None)]);
+        sendmail.priority = 20;
+
+        let mut main = Repo::new(Url::parse("file:///tmp/zpstestsolver/main").unwrap(), 10, true);
+        main.load(vec![
+            package("nachos", "1.0.0", vec![depends("mail-transport-agent")]),
+            package("tacos", "1.0.0", vec![req("mail-transport-agent", RequirementMethod::Depends, Comparator::GTE, Some("2.0.0"))]),
+            package("postfix", "3.4.0", vec![provides("mail-transport-agent", Some("1.0.0"))]),
+            sendmail,
+        ]);
+
+        let mut extra = Repo::new(Url::parse("file:///tmp/zpstestsolver/extra").unwrap(), 20, true);
+        extra.load(vec![package("exim", "4.93.0", vec![provides("mail-transport-agent", Some("2.0.0"))])]);
+
+        let solver = Solver::new(OSArch::new(OS::Linux, Arch::X8664), vec![], &[main.clone(), extra.clone()]);
+
+        // The repo with the better priority wins, then the package's own priority
+        let mut request = Request::new();
+        request.install(depends("nachos"));
+        assert_eq!(plan(&solver.solve(&request)?), vec![
+            "install postfix@3.4.0:20200415T194203Z",
+            "install nachos@1.0.0:20200415T194203Z",
+        ]);
+
+        // Only exim provides a version recent enough
+        let mut request = Request::new();
+        request.install(depends("tacos"));
+        assert_eq!(plan(&solver.solve(&request)?), vec![
+            "install exim@4.93.0:20200415T194203Z",
+            "install tacos@1.0.0:20200415T194203Z",
+        ]);
+
+        // Removing the provider in use pulls in the next best one
+        let installed = vec![
+            package("nachos", "1.0.0", vec![depends("mail-transport-agent")]),
+            package("postfix", "3.4.0", vec![provides("mail-transport-agent", Some("1.0.0"))]),
+        ];
+        let solver = Solver::new(OSArch::new(OS::Linux, Arch::X8664), installed.clone(), &[main, extra]);
+
+        let mut request = Request::new();
+        request.remove(depends("postfix"));
+        assert_eq!(plan(&solver.solve(&request)?), vec![
+            "remove postfix@3.4.0:20200415T194203Z",
+            "install sendmail@8.15.0:20200415T194203Z",
+        ]);
+
+        let solver = Solver::new(OSArch::new(OS::Linux, Arch::X8664), installed, &[]);
+        assert_eq!(
+            solver.solve(&request).err().unwrap().to_string(),
+            "no solution: nachos@1.0.0:20200415T194203Z requires mail-transport-agent, \
+             but postfix@3.4.0:20200415T194203Z is being removed"
+        );
+
+        Ok(())
+    }
+}
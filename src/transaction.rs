@@ -0,0 +1,411 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * Copyright 2020 Zachary Schneider
+ */
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Error};
+use event_emitter_rs::EventEmitter;
+use serde::Deserialize;
+
+use crate::{Emitter, Operation, OperationMethod, Phase};
+use crate::action::{Action, Manifest};
+use crate::config::Config;
+use crate::db::State;
+use crate::journal::Journal;
+use crate::provider::{self, Options, provider_for};
+use crate::zpkg::reader::Reader;
+
+// An operation with everything needed to carry it out, gathered before the tree is touched
+enum Step {
+    Install(Reader),
+    Remove(Manifest),
+}
+
+// Applies an ordered plan to the tree, package state is only updated once an operation's filesystem work is done
+pub struct Transaction {
+    emitter: EventEmitter,
+
+    tree: PathBuf,
+    cache_path: PathBuf,
+    tmp_path: PathBuf,
+
+    state: State,
+    journal: Journal,
+    operations: Vec<Operation>,
+}
+
+impl Transaction {
+    pub fn new(config: &Config, operations: Vec<Operation>) -> Transaction {
+        Transaction {
+            emitter: EventEmitter::new(),
+            tree: config.tree(),
+            cache_path: config.cache_path(),
+            tmp_path: config.tmp_path(),
+            state: State::new(config.db_path().to_str().unwrap()),
+            journal: Journal::new(config),
+            operations,
+        }
+    }
+
+    pub fn realize(&mut self) -> Result<(), Error> {
+        if self.journal.recover(&mut self.state)? {
+            self.emitter.sync_emit("warn", "rolled back an interrupted transaction".to_string());
+        }
+
+        let steps = self.prepare()?;
+
+        std::fs::create_dir_all(&self.tmp_path)?;
+
+        let (paths, packages) = self.touched(&steps)?;
+        self.journal.record(&paths, packages)?;
+
+        for step in steps {
+            let result = match step {
+                Step::Install(reader) => self.install(reader),
+                Step::Remove(manifest) => self.remove(manifest),
+            };
+
+            if let Err(err) = result {
+                self.journal.rollback(&mut self.state)
+                    .map_err(|rollback_err| anyhow!("{}, rollback failed: {}", err, rollback_err))?;
+
+                return Err(err);
+            }
+        }
+
+        self.journal.commit()
+    }
+
+    // Opens every package and checks the plan against installed state, nothing is written
+    fn prepare(&mut self) -> Result<Vec<Step>, Error> {
+        let mut steps: Vec<Step> = Vec::new();
+
+        // Owner of each path as the plan runs, and whether it is installed or earlier in the plan
+        let mut owners: HashMap<String, (String, bool)> = HashMap::new();
+
+        for installed in self.state.pkg_list()? {
+            for path in Self::paths(&installed) {
+                owners.insert(path, (installed.zpkg.name.clone(), true));
+            }
+        }
+
+        for operation in self.operations.iter() {
+            match operation.method {
+                OperationMethod::Install => {
+                    let mut reader = Reader::new(&self.cache_path.join(operation.package.file_name()), &self.tmp_path);
+                    reader.read()?;
+
+                    Self::check_conflicts(reader.manifest.as_ref().unwrap(), &mut owners)?;
+
+                    steps.push(Step::Install(reader));
+                },
+                OperationMethod::Remove => {
+                    match self.state.pkg_get(operation.package.name.clone())? {
+                        Some(manifest) => {
+                            owners.retain(|_, (name, _)| name != &manifest.zpkg.name);
+                            steps.push(Step::Remove(manifest));
+                        },
+                        None => return Err(anyhow!("cannot remove {}, package is not installed", operation.package.name)),
+                    }
+                },
+                OperationMethod::NoOp => (),
+            }
+        }
+
+        Ok(steps)
+    }
+
+    // Every path the steps may change, with the installed state of each package involved
+    fn touched(&mut self, steps: &[Step]) -> Result<(BTreeSet<String>, Vec<(String, Option<Manifest>)>), Error> {
+        let mut paths: BTreeSet<String> = BTreeSet::new();
+        let mut packages: Vec<(String, Option<Manifest>)> = Vec::new();
+
+        for step in steps {
+            let manifest = match step {
+                Step::Install(reader) => reader.manifest.as_ref().unwrap(),
+                Step::Remove(manifest) => manifest,
+            };
+
+            // A package may appear in several steps, each adding paths of its own
+            if !packages.iter().any(|(name, _)| name == &manifest.zpkg.name) {
+                let previous = self.state.pkg_get(manifest.zpkg.name.clone())?;
+
+                if let Some(previous) = previous.as_ref() {
+                    paths.extend(previous.dirs.iter().map(|d| d.path.clone()));
+                    paths.extend(Self::paths(previous));
+                }
+
+                packages.push((manifest.zpkg.name.clone(), previous));
+            }
+
+            paths.extend(manifest.dirs.iter().map(|d| d.path.clone()));
+            paths.extend(Self::paths(manifest));
+        }
+
+        Ok((paths, packages))
+    }
+
+    // Paths may only belong to one package, directories excepted, those of the manifest are claimed once checked
+    fn check_conflicts(manifest: &Manifest, owners: &mut HashMap<String, (String, bool)>) -> Result<(), Error> {
+        // An upgrade takes over everything the version it replaces owned
+        owners.retain(|_, (name, _)| name != &manifest.zpkg.name);
+
+        let mut paths: Vec<String> = Self::paths(manifest).into_iter().collect();
+        paths.sort();
+
+        for path in paths.iter() {
+            match owners.get(path) {
+                Some((name, true)) => return Err(anyhow!("{} conflicts with installed package {}: {}", manifest.zpkg.name, name, path)),
+                Some((name, false)) => return Err(anyhow!("{} conflicts with package {} in the same transaction: {}", manifest.zpkg.name, name, path)),
+                None => (),
+            }
+        }
+
+        for path in paths {
+            owners.insert(path, (manifest.zpkg.name.clone(), false));
+        }
+
+        Ok(())
+    }
+
+    fn install(&mut self, mut reader: Reader) -> Result<(), Error> {
+        let payload = reader.payload()?;
+        let manifest = reader.manifest.take().unwrap();
+        let previous = self.state.pkg_get(manifest.zpkg.name.clone())?;
+
+        self.emitter.sync_emit("info", format!("install {}@{}", manifest.zpkg.name, manifest.zpkg.version));
+
+        // Dirs precede files and links follow the files they point at
+        for action in manifest.actions() {
+            provider_for(action).realize(self.options(), Phase::Install, Some(&payload), None)?;
+        }
+
+        // Anything the previous version shipped which this one doesn't is cleaned up
+        if let Some(previous) = previous {
+            let paths = Self::paths(&manifest);
+            let dirs: HashSet<&String> = manifest.dirs.iter().map(|d| &d.path).collect();
+
+            let mut stale = Manifest::new(previous.zpkg.clone());
+            stale.dirs = previous.dirs.into_iter().filter(|d| !dirs.contains(&d.path)).collect();
+            stale.files = previous.files.into_iter().filter(|f| !paths.contains(&f.path)).collect();
+            stale.symlinks = previous.symlinks.into_iter().filter(|l| !paths.contains(&l.path)).collect();
+            stale.hardlinks = previous.hardlinks.into_iter().filter(|l| !paths.contains(&l.path)).collect();
+
+            self.remove_actions(&stale)?;
+        }
+
+        self.state.pkg_put(manifest)?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, manifest: Manifest) -> Result<(), Error> {
+        self.emitter.sync_emit("info", format!("remove {}@{}", manifest.zpkg.name, manifest.zpkg.version));
+
+        self.remove_actions(&manifest)?;
+
+        self.state.pkg_del(manifest.zpkg.name)?;
+
+        Ok(())
+    }
+
+    fn remove_actions(&mut self, manifest: &Manifest) -> Result<(), Error> {
+        let shared: HashSet<String> = self.state.pkg_list()?
+            .into_iter()
+            .filter(|m| m.zpkg.name != manifest.zpkg.name)
+            .flat_map(|m| m.dirs.into_iter().map(|d| d.path))
+            .collect();
+
+        for path in provider::remove(manifest, self.options(), &shared)? {
+            self.emitter.sync_emit("warn", format!("{} was modified locally, removed anyway", path));
+        }
+
+        Ok(())
+    }
+
+    fn options(&self) -> Options {
+        let mut options = Options::new();
+        options.target_path = Some(self.tree.clone());
+        options.work_path = Some(self.tmp_path.clone());
+        options
+    }
+
+    fn paths(manifest: &Manifest) -> HashSet<String> {
+        manifest.files.iter().map(|f| f.key())
+            .chain(manifest.symlinks.iter().map(|l| l.key()))
+            .chain(manifest.hardlinks.iter().map(|l| l.key()))
+            .collect()
+    }
+}
+
+impl Emitter for Transaction {
+    fn on<F, T>(&mut self, event: &str, callback: F) -> String
+        where
+                for<'de> T: Deserialize<'de>,
+                F: Fn(T) + 'static + Sync + Send
+    {
+        let id = self.emitter.on_limited(event, None, callback);
+        return id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Package;
+    use crate::zpkg;
+    use crate::zpkg::reader::Reader;
+    use crate::zpkg::tests::{test_root, zpkgfile};
+    use std::path::Path;
+
+    // Builds a package from files under proto into the cache of the tree at root
+    fn build(root: &Path, config: &Config, zpkgfile: &str, files: &[(&str, &str)]) -> Result<Package, Error> {
+        let src = root.join("src");
+        let _ = std::fs::remove_dir_all(&src);
+
+        for (path, contents) in files {
+            let path = src.join("proto").join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, contents)?;
+        }
+
+        let path = zpkg::tests::build(&src, zpkgfile, &config.cache_path())?;
+
+        let mut reader = Reader::new(&path, root);
+        reader.read()?;
+
+        Package::from(reader.manifest.take().unwrap())
+    }
+
+    #[test]
+    fn test_transaction() -> Result<(), Error> {
+        let root = test_root("zpstesttransaction")?;
+        let tree = root.join("tree");
+        let config = Config::for_tree(&tree);
+
+        let v1 = build(&root, &config, &zpkgfile("nachos", "1.0.0"), &[("usr/bin/nachos", "v1"), ("usr/share/nachos/old", "old")])?;
+        let v2 = build(&root, &config, &zpkgfile("nachos", "2.0.0"), &[("usr/bin/nachos", "v2"), ("usr/share/nachos/new", "new")])?;
+        let salsa = build(&root, &config, &zpkgfile("salsa", "1.0.0"), &[("usr/bin/salsa", "salsa")])?;
+        let conflict = build(&root, &config, &zpkgfile("queso", "1.0.0"), &[("usr/bin/salsa", "queso")])?;
+
+        Transaction::new(&config, vec![
+            Operation::new(OperationMethod::Install, v1),
+            Operation::new(OperationMethod::Install, salsa.clone()),
+        ]).realize()?;
+
+        assert_eq!(std::fs::read(tree.join("usr/bin/nachos"))?, b"v1");
+        assert_eq!(std::fs::read(tree.join("usr/bin/salsa"))?, b"salsa");
+
+        // Upgrades replace contents and drop paths the new version no longer ships
+        Transaction::new(&config, vec![Operation::new(OperationMethod::Install, v2)]).realize()?;
+
+        assert_eq!(std::fs::read(tree.join("usr/bin/nachos"))?, b"v2");
+        assert!(tree.join("usr/share/nachos/new").exists());
+        assert!(!tree.join("usr/share/nachos/old").exists());
+
+        let err = Transaction::new(&config, vec![Operation::new(OperationMethod::Install, conflict)]).realize().unwrap_err();
+        assert_eq!(err.to_string(), "queso conflicts with installed package salsa: usr/bin/salsa");
+        assert_eq!(std::fs::read(tree.join("usr/bin/salsa"))?, b"salsa");
+
+        // Directories shared with nachos stay behind
+        Transaction::new(&config, vec![Operation::new(OperationMethod::Remove, salsa.clone())]).realize()?;
+
+        assert!(!tree.join("usr/bin/salsa").exists());
+        assert!(tree.join("usr/bin/nachos").exists());
+
+        let mut state = State::new(config.db_path().to_str().unwrap());
+        let installed: Vec<String> = state.pkg_list()?.into_iter().map(|m| m.zpkg.name).collect();
+        assert_eq!(installed, vec!["nachos".to_string()]);
+
+        assert!(Transaction::new(&config, vec![Operation::new(OperationMethod::Remove, salsa.clone())]).realize().is_err());
+
+        // A failure part way through puts the tree and state back as they were
+        std::fs::create_dir_all(tree.join("usr/bin/broken"))?;
+        std::fs::write(tree.join("usr/bin/broken/keep"), "")?;
+        let broken = build(&root, &config, &zpkgfile("broken", "1.0.0"), &[("usr/bin/broken", "broken")])?;
+
+        let err = Transaction::new(&config, vec![
+            Operation::new(OperationMethod::Install, salsa),
+            Operation::new(OperationMethod::Remove, Package::from(state.pkg_get("nachos".to_string())?.unwrap())?),
+            Operation::new(OperationMethod::Install, broken),
+        ]).realize();
+
+        assert!(err.is_err());
+        assert!(!tree.join("usr/bin/salsa").exists());
+        assert_eq!(std::fs::read(tree.join("usr/bin/nachos"))?, b"v2");
+        assert!(tree.join("usr/bin/broken/keep").exists());
+        assert!(!Journal::new(&config).pending());
+
+        let installed: Vec<String> = state.pkg_list()?.into_iter().map(|m| m.zpkg.name).collect();
+        assert_eq!(installed, vec!["nachos".to_string()]);
+
+        // Paths only the replacement ships are rolled back too, even after a remove of the same package
+        std::fs::create_dir_all(tree.join("usr/share/zzz/keep"))?;
+        let v3 = build(&root, &config, &zpkgfile("nachos", "3.0.0"), &[("usr/bin/nachos-v3", "v3"), ("usr/share/zzz", "v3")])?;
+
+        let err = Transaction::new(&config, vec![
+            Operation::new(OperationMethod::Remove, Package::from(state.pkg_get("nachos".to_string())?.unwrap())?),
+            Operation::new(OperationMethod::Install, v3),
+        ]).realize();
+
+        assert!(err.is_err());
+        assert!(!tree.join("usr/bin/nachos-v3").exists());
+        assert_eq!(std::fs::read(tree.join("usr/bin/nachos"))?, b"v2");
+        assert!(state.pkg_get("nachos".to_string())?.unwrap().zpkg.version.starts_with("2.0.0:"));
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_conflicts() -> Result<(), Error> {
+        let root = test_root("zpstesttransactionconflicts")?;
+        let tree = root.join("tree");
+        let config = Config::for_tree(&tree);
+
+        let salsa = build(&root, &config, &zpkgfile("salsa", "1.0.0"), &[("usr/bin/salsa", "salsa")])?;
+        let queso = build(&root, &config, &zpkgfile("queso", "1.0.0"), &[("usr/bin/salsa", "queso")])?;
+        let chips = build(&root, &config, &zpkgfile("chips", "1.0.0"), &[("usr/bin/dip", "chips")])?;
+        let dip = build(&root, &config, &zpkgfile("dip", "1.0.0"), &[("usr/bin/dip", "dip")])?;
+
+        Transaction::new(&config, vec![Operation::new(OperationMethod::Install, salsa.clone())]).realize()?;
+
+        // Installing first would leave the path to be removed with salsa
+        let err = Transaction::new(&config, vec![
+            Operation::new(OperationMethod::Install, queso.clone()),
+            Operation::new(OperationMethod::Remove, salsa.clone()),
+        ]).realize().unwrap_err();
+        assert_eq!(err.to_string(), "queso conflicts with installed package salsa: usr/bin/salsa");
+
+        // A path freed by an earlier step may be taken over
+        Transaction::new(&config, vec![
+            Operation::new(OperationMethod::Remove, salsa),
+            Operation::new(OperationMethod::Install, queso),
+        ]).realize()?;
+
+        assert_eq!(std::fs::read(tree.join("usr/bin/salsa"))?, b"queso");
+
+        // Packages within one plan can't share a path either
+        let err = Transaction::new(&config, vec![
+            Operation::new(OperationMethod::Install, chips),
+            Operation::new(OperationMethod::Install, dip),
+        ]).realize().unwrap_err();
+        assert_eq!(err.to_string(), "dip conflicts with package chips in the same transaction: usr/bin/dip");
+        assert!(!tree.join("usr/bin/dip").exists());
+
+        let mut state = State::new(config.db_path().to_str().unwrap());
+        let installed: Vec<String> = state.pkg_list()?.into_iter().map(|m| m.zpkg.name).collect();
+        assert_eq!(installed, vec!["queso".to_string()]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+use crate::action::{Dir, File, HardLink, Manifest, Requirement, SymLink, Zpkg};
+use crate::platform::{Arch, OSArch, OS};
+use crate::zpf::lexer::{ParseError, Pos};
+use crate::zpf::parser::{Attribute, Block, Body, Value};
+
+const DEFAULT_OWNER: &str = "root";
+const DEFAULT_GROUP: &str = "root";
+const DEFAULT_DIR_MODE: u32 = 0o755;
+const DEFAULT_FILE_MODE: u32 = 0o644;
+const DEFAULT_METHOD: &str = "depends";
+const DEFAULT_OPERATION: &str = "ANY";
+
+// Variables provided by the build target, these may not be redefined
+const RESERVED: &[&str] = &["os", "arch"];
+
+pub struct Evaluator {
+    target: OSArch,
+    vars: HashMap<String, String>,
+
+    owner: String,
+    group: String,
+
+    zpkg: Option<Zpkg>,
+    requirements: Vec<Requirement>,
+    dirs: Vec<Dir>,
+    files: Vec<File>,
+    symlinks: Vec<SymLink>,
+    hardlinks: Vec<HardLink>,
+    paths: HashSet<String>,
+}
+
+impl Evaluator {
+    pub fn new(target: OSArch) -> Evaluator {
+        let mut vars = HashMap::new();
+
+        vars.insert("os".to_string(), target.os().to_string());
+        vars.insert("arch".to_string(), target.arch().to_string());
+
+        Evaluator {
+            target,
+            vars,
+            owner: DEFAULT_OWNER.to_string(),
+            group: DEFAULT_GROUP.to_string(),
+            zpkg: None,
+            requirements: Vec::new(),
+            dirs: Vec::new(),
+            files: Vec::new(),
+            symlinks: Vec::new(),
+            hardlinks: Vec::new(),
+            paths: HashSet::new(),
+        }
+    }
+
+    // Ownership for Dir and File blocks which don't set their own
+    pub fn ownership(mut self, owner: Option<&str>, group: Option<&str>) -> Evaluator {
+        if let Some(owner) = owner {
+            self.owner = owner.to_string();
+        }
+
+        if let Some(group) = group {
+            self.group = group.to_string();
+        }
+
+        self
+    }
+
+    pub fn eval(mut self, body: &Body) -> Result<Manifest, ParseError> {
+        // Top level attributes define variables, in order of appearance
+        for attr in body.attributes.iter() {
+            self.define(attr)?;
+        }
+
+        self.blocks(&body.blocks, true)?;
+
+        let zpkg = match self.zpkg {
+            Some(zpkg) => zpkg,
+            None => return Err(ParseError::new(Pos { line: 1, column: 1 }, "missing Zpkg block")),
+        };
+
+        let mut manifest = Manifest::new(zpkg);
+        manifest.requirements = self.requirements;
+        manifest.dirs = self.dirs;
+        manifest.files = self.files;
+        manifest.symlinks = self.symlinks;
+        manifest.hardlinks = self.hardlinks;
+
+        Ok(manifest)
+    }
+
+    fn define(&mut self, attr: &Attribute) -> Result<(), ParseError> {
+        if RESERVED.contains(&attr.name.as_str()) {
+            return Err(ParseError::new(attr.pos, format!("variable '{}' is reserved", attr.name)));
+        }
+
+        if self.vars.contains_key(&attr.name) {
+            return Err(ParseError::new(attr.pos, format!("duplicate variable '{}'", attr.name)));
+        }
+
+        let value = match &attr.value {
+            Value::String(value) => self.interpolate(value, attr.pos)?,
+            Value::Number(value) => value.clone(),
+            Value::Bool(value) => value.to_string(),
+        };
+
+        self.vars.insert(attr.name.clone(), value);
+
+        Ok(())
+    }
+
+    fn blocks(&mut self, blocks: &[Block], top: bool) -> Result<(), ParseError> {
+        for block in blocks.iter() {
+            match block.kind.as_str() {
+                "Zpkg" if top => {
+                    if self.zpkg.is_some() {
+                        return Err(ParseError::new(block.pos, "duplicate Zpkg block"));
+                    }
+                    self.zpkg = Some(self.zpkg(block)?);
+                }
+                "Platform" => self.platform(block)?,
+                "Requirement" => {
+                    let requirement = self.requirement(block)?;
+                    self.requirements.push(requirement)
+                }
+                "Dir" => {
+                    let path = self.path(block)?;
+                    let attrs = Attrs::new(self, block, &["owner", "group", "mode"])?;
+
+                    let dir = Dir {
+                        path,
+                        owner: attrs.string("owner")?.unwrap_or_else(|| self.owner.clone()),
+                        group: attrs.string("group")?.unwrap_or_else(|| self.group.clone()),
+                        mode: attrs.mode("mode")?.unwrap_or(DEFAULT_DIR_MODE),
+                    };
+
+                    self.dirs.push(dir)
+                }
+                "File" => {
+                    let path = self.path(block)?;
+                    let attrs = Attrs::new(self, block, &["owner", "group", "mode"])?;
+
+                    let file = File {
+                        path,
+                        owner: attrs.string("owner")?.unwrap_or_else(|| self.owner.clone()),
+                        group: attrs.string("group")?.unwrap_or_else(|| self.group.clone()),
+                        mode: attrs.mode("mode")?.unwrap_or(DEFAULT_FILE_MODE),
+                        digest: "".to_string(),
+                        offset: 0,
+                        csize: 0,
+                        size: 0,
+                    };
+
+                    self.files.push(file)
+                }
+                "SymLink" => {
+                    let path = self.path(block)?;
+                    let attrs = Attrs::new(self, block, &["target", "owner", "group"])?;
+
+                    let symlink = SymLink {
+                        path,
+                        target: attrs.required("target")?,
+                        owner: attrs.string("owner")?.unwrap_or_else(|| self.owner.clone()),
+                        group: attrs.string("group")?.unwrap_or_else(|| self.group.clone()),
+                    };
+
+                    self.symlinks.push(symlink)
+                }
+                "HardLink" => {
+                    let path = self.path(block)?;
+                    let attrs = Attrs::new(self, block, &["target"])?;
+
+                    let hardlink = HardLink {
+                        path,
+                        target: attrs.required("target")?,
+                    };
+
+                    self.hardlinks.push(hardlink)
+                }
+                kind => {
+                    return Err(ParseError::new(
+                        block.pos,
+                        format!("unexpected block type '{}'", kind),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Platform blocks apply when any label matches the target, "any" acts as a wildcard
+    fn platform(&mut self, block: &Block) -> Result<(), ParseError> {
+        if let Some(attr) = block.body.attributes.first() {
+            return Err(ParseError::new(
+                attr.pos,
+                format!("unexpected attribute '{}' in Platform", attr.name),
+            ));
+        }
+
+        if block.labels.is_empty() {
+            return Err(ParseError::new(block.pos, "Platform block requires at least one label"));
+        }
+
+        let expanded = self.target.expand();
+        let mut matched = false;
+
+        for label in block.labels.iter() {
+            let platform = OSArch::from_str(label)
+                .map_err(|_| ParseError::new(block.pos, format!("invalid platform '{}'", label)))?;
+
+            matched = matched || expanded.contains(&platform);
+        }
+
+        if matched {
+            self.blocks(&block.body.blocks, false)?;
+        }
+
+        Ok(())
+    }
+
+    fn zpkg(&self, block: &Block) -> Result<Zpkg, ParseError> {
+        let attrs = Attrs::new(
+            self,
+            block,
+            &["version", "publisher", "summary", "description", "os", "arch"],
+        )?;
+
+        let name = self.label(block)?;
+        let version = attrs.required("version")?;
+        let publisher = attrs.required("publisher")?;
+        let summary = attrs.required("summary")?;
+
+        // A package may pin its platform, e.g. os = "any" for scripts
+        let os = match attrs.string("os")? {
+            Some(os) => OS::from_str(&os)
+                .map_err(|_| ParseError::new(attrs.pos("os"), format!("invalid os '{}'", os)))?,
+            None => self.target.os(),
+        };
+
+        let arch = match attrs.string("arch")? {
+            Some(arch) => Arch::from_str(&arch)
+                .map_err(|_| ParseError::new(attrs.pos("arch"), format!("invalid arch '{}'", arch)))?,
+            None => self.target.arch(),
+        };
+
+        Ok(Zpkg {
+            name,
+            version,
+            publisher,
+            os: os.to_string(),
+            arch: arch.to_string(),
+            description: attrs.string("description")?.unwrap_or_else(|| summary.clone()),
+            summary,
+        })
+    }
+
+    // Depends on any version unless told otherwise
+    fn requirement(&self, block: &Block) -> Result<Requirement, ParseError> {
+        let attrs = Attrs::new(self, block, &["method", "operation", "version"])?;
+
+        let requirement = Requirement {
+            name: self.label(block)?,
+            method: attrs.string("method")?.unwrap_or_else(|| DEFAULT_METHOD.to_string()),
+            operation: attrs.string("operation")?.unwrap_or_else(|| DEFAULT_OPERATION.to_string()),
+            version: attrs.string("version")?.unwrap_or_default(),
+        };
+
+        crate::Requirement::from_action(&requirement)
+            .map_err(|err| ParseError::new(block.pos, err.to_string()))?;
+
+        if self.requirements.contains(&requirement) {
+            return Err(ParseError::new(block.pos, format!("duplicate requirement '{}'", requirement.name)));
+        }
+
+        Ok(requirement)
+    }
+
+    fn label(&self, block: &Block) -> Result<String, ParseError> {
+        match block.labels.as_slice() {
+            [label] if !label.is_empty() => self.interpolate(label, block.pos),
+            _ => Err(ParseError::new(
+                block.pos,
+                format!("{} block requires exactly one non-empty label", block.kind),
+            )),
+        }
+    }
+
+    // Normalizes a manifest path, they are always relative to the target tree
+    fn path(&mut self, block: &Block) -> Result<String, ParseError> {
+        let label = self.label(block)?;
+        let mut parts: Vec<&str> = Vec::new();
+
+        for component in Path::new(&label).components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_str().unwrap()),
+                Component::RootDir | Component::CurDir => (),
+                _ => return Err(ParseError::new(block.pos, format!("invalid path '{}'", label))),
+            }
+        }
+
+        if parts.is_empty() {
+            return Err(ParseError::new(block.pos, format!("invalid path '{}'", label)));
+        }
+
+        let path = parts.join("/");
+
+        if !self.paths.insert(path.clone()) {
+            return Err(ParseError::new(block.pos, format!("duplicate action for path '{}'", path)));
+        }
+
+        Ok(path)
+    }
+
+    // Expands ${name} references, $${ escapes a literal ${
+    fn interpolate(&self, value: &str, pos: Pos) -> Result<String, ParseError> {
+        let mut result = String::new();
+        let mut rest = value;
+
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                result.push_str(&rest[..start - 1]);
+                result.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            result.push_str(&rest[..start]);
+
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(ParseError::new(pos, "unterminated variable reference")),
+            };
+
+            let name = rest[start + 2..end].trim();
+
+            match self.vars.get(name) {
+                Some(var) => result.push_str(var),
+                None => return Err(ParseError::new(pos, format!("undefined variable '{}'", name))),
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        result.push_str(rest);
+
+        Ok(result)
+    }
+}
+
+struct Attrs<'a> {
+    eval: &'a Evaluator,
+    block: &'a Block,
+}
+
+impl<'a> Attrs<'a> {
+    fn new(eval: &'a Evaluator, block: &'a Block, allowed: &[&str]) -> Result<Attrs<'a>, ParseError> {
+        if let Some(nested) = block.body.blocks.first() {
+            return Err(ParseError::new(
+                nested.pos,
+                format!("unexpected block '{}' in {}", nested.kind, block.kind),
+            ));
+        }
+
+        let mut seen: HashSet<&str> = HashSet::new();
+
+        for attr in block.body.attributes.iter() {
+            if !allowed.contains(&attr.name.as_str()) {
+                return Err(ParseError::new(
+                    attr.pos,
+                    format!("unknown attribute '{}' in {}", attr.name, block.kind),
+                ));
+            }
+
+            if !seen.insert(attr.name.as_str()) {
+                return Err(ParseError::new(attr.pos, format!("duplicate attribute '{}'", attr.name)));
+            }
+        }
+
+        Ok(Attrs { eval, block })
+    }
+
+    fn get(&self, name: &str) -> Option<&'a Attribute> {
+        self.block.body.attributes.iter().find(|attr| attr.name == name)
+    }
+
+    fn pos(&self, name: &str) -> Pos {
+        self.get(name).map(|attr| attr.pos).unwrap_or(self.block.pos)
+    }
+
+    fn string(&self, name: &str) -> Result<Option<String>, ParseError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Attribute { value: Value::String(value), pos, .. }) => {
+                Ok(Some(self.eval.interpolate(value, *pos)?))
+            }
+            Some(attr) => Err(ParseError::new(attr.pos, format!("attribute '{}' must be a string", name))),
+        }
+    }
+
+    fn required(&self, name: &str) -> Result<String, ParseError> {
+        match self.string(name)? {
+            Some(value) if !value.is_empty() => Ok(value),
+            _ => Err(ParseError::new(
+                self.block.pos,
+                format!("{} block is missing required attribute '{}'", self.block.kind, name),
+            )),
+        }
+    }
+
+    // Modes are octal whether written as a number or a string
+    fn mode(&self, name: &str) -> Result<Option<u32>, ParseError> {
+        let attr = match self.get(name) {
+            None => return Ok(None),
+            Some(attr) => attr,
+        };
+
+        let raw = match &attr.value {
+            Value::String(value) => self.eval.interpolate(value, attr.pos)?,
+            Value::Number(value) => value.clone(),
+            Value::Bool(_) => return Err(ParseError::new(attr.pos, format!("attribute '{}' must be an octal mode", name))),
+        };
+
+        match u32::from_str_radix(&raw, 8) {
+            Ok(mode) if mode <= 0o7777 => Ok(Some(mode)),
+            _ => Err(ParseError::new(attr.pos, format!("invalid mode '{}'", raw))),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub pos: Pos,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(pos: Pos, message: S) -> ParseError {
+        ParseError {
+            pos,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.pos, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Ident(String),
+    String(String),
+    Number(String),
+    Assign,
+    LBrace,
+    RBrace,
+    EOF,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "identifier '{}'", name),
+            Token::String(_) => write!(f, "string"),
+            Token::Number(num) => write!(f, "number '{}'", num),
+            Token::Assign => write!(f, "'='"),
+            Token::LBrace => write!(f, "'{{'"),
+            Token::RBrace => write!(f, "'}}'"),
+            Token::EOF => write!(f, "end of file"),
+        }
+    }
+}
+
+pub struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: Pos,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: src.chars().peekable(),
+            pos: Pos { line: 1, column: 1 },
+        }
+    }
+
+    pub fn tokenize(mut self) -> Result<Vec<(Token, Pos)>, ParseError> {
+        let mut tokens = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            let pos = self.pos;
+
+            let token = match self.chars.peek() {
+                None => Token::EOF,
+                Some('=') => {
+                    self.bump();
+                    Token::Assign
+                }
+                Some('{') => {
+                    self.bump();
+                    Token::LBrace
+                }
+                Some('}') => {
+                    self.bump();
+                    Token::RBrace
+                }
+                Some('"') => self.string()?,
+                Some(c) if c.is_ascii_digit() => self.number(),
+                Some(c) if c.is_alphabetic() || *c == '_' => self.ident(),
+                Some(c) => return Err(ParseError::new(pos, format!("unexpected character '{}'", c))),
+            };
+
+            let done = token == Token::EOF;
+            tokens.push((token, pos));
+
+            if done {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => self.skip_line(),
+                Some('/') => {
+                    let mut ahead = self.chars.clone();
+                    ahead.next();
+
+                    if ahead.peek() != Some(&'/') {
+                        return;
+                    }
+
+                    self.skip_line()
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.bump() {
+            if c == '\n' {
+                return;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Token {
+        let mut ident = String::new();
+
+        while let Some(c) = self.chars.peek() {
+            if !(c.is_alphanumeric() || *c == '_' || *c == '-') {
+                break;
+            }
+            ident.push(self.bump().unwrap());
+        }
+
+        Token::Ident(ident)
+    }
+
+    fn number(&mut self) -> Token {
+        let mut number = String::new();
+
+        while let Some(c) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            number.push(self.bump().unwrap());
+        }
+
+        Token::Number(number)
+    }
+
+    fn string(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
+        let mut value = String::new();
+
+        // Opening quote
+        self.bump();
+
+        loop {
+            let pos = self.pos;
+
+            match self.bump() {
+                None | Some('\n') => return Err(ParseError::new(start, "unterminated string")),
+                Some('"') => return Ok(Token::String(value)),
+                Some('\\') => match self.bump() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => return Err(ParseError::new(pos, format!("invalid escape '\\{}'", c))),
+                    None => return Err(ParseError::new(start, "unterminated string")),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() -> Result<(), ParseError> {
+        let tokens = Lexer::new("# comment\nDir \"etc\" {\n  mode = 0755 // trailing\n}\n").tokenize()?;
+
+        let expected = vec![
+            (Token::Ident("Dir".to_string()), Pos { line: 2, column: 1 }),
+            (Token::String("etc".to_string()), Pos { line: 2, column: 5 }),
+            (Token::LBrace, Pos { line: 2, column: 11 }),
+            (Token::Ident("mode".to_string()), Pos { line: 3, column: 3 }),
+            (Token::Assign, Pos { line: 3, column: 8 }),
+            (Token::Number("0755".to_string()), Pos { line: 3, column: 10 }),
+            (Token::RBrace, Pos { line: 4, column: 1 }),
+            (Token::EOF, Pos { line: 5, column: 1 }),
+        ];
+
+        assert_eq!(tokens, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let err = Lexer::new("Zpkg \"zps {\n").tokenize().unwrap_err();
+
+        assert_eq!(err.to_string(), "1:6: unterminated string");
+    }
+}
@@ -0,0 +1,248 @@
+mod eval;
+mod lexer;
+mod parser;
+
+pub use lexer::ParseError;
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Error};
+
+use crate::action::Manifest;
+use crate::platform::OSArch;
+use eval::Evaluator;
+use parser::{Body, Parser};
+
+/// A parsed Zpkgfile, the declarative description of a package.
+///
+/// Top level attributes define variables which may be referenced from any
+/// string as `${name}`, `${os}` and `${arch}` are provided by the build target.
+/// Platform blocks only apply when one of their labels matches the target.
+/// Requirement blocks default to method "depends" and operation "ANY".
+///
+/// ```text
+/// version = "1.0.0"
+///
+/// Zpkg "zps" {
+///     version     = "${version}"
+///     publisher   = "zps.io"
+///     summary     = "The last word in package management"
+///     description = "ZPS package manager"
+/// }
+///
+/// Requirement "openssl" {
+///     operation = "GTE"
+///     version   = "1.1.1"
+/// }
+///
+/// Dir "usr/bin" {
+///     mode = 0755
+/// }
+///
+/// File "usr/bin/zps" {
+///     owner = "root"
+///     group = "root"
+///     mode  = 0755
+/// }
+///
+/// Platform "linux-any" {
+///     File "usr/lib/${arch}/libzps.so.1" {}
+///
+///     SymLink "usr/lib/${arch}/libzps.so" {
+///         target = "libzps.so.1"
+///     }
+/// }
+/// ```
+pub struct Zpkgfile {
+    body: Body,
+}
+
+impl Zpkgfile {
+    pub fn load(path: &Path) -> Result<Zpkgfile, Error> {
+        let src = fs::read_to_string(path)?;
+
+        Self::parse(&src).map_err(|err| anyhow!("{}:{}", path.display(), err))
+    }
+
+    pub fn parse(src: &str) -> Result<Zpkgfile, ParseError> {
+        Ok(Zpkgfile {
+            body: Parser::parse(src)?,
+        })
+    }
+
+    pub fn manifest(&self, target: OSArch) -> Result<Manifest, ParseError> {
+        Evaluator::new(target).eval(&self.body)
+    }
+
+    // As manifest, with ownership defaults for entries that don't specify their own
+    pub fn manifest_owned(&self, target: OSArch, owner: Option<&str>, group: Option<&str>) -> Result<Manifest, ParseError> {
+        Evaluator::new(target).ownership(owner, group).eval(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::{Arch, OS};
+
+    const ZPKGFILE: &str = r#"
+Zpkg "zps" {
+    version     = "1.0.0"
+    publisher   = "zps.io"
+    summary     = "The last word in package management"
+    os          = "linux"
+    arch        = "x86_64"
+}
+
+Requirement "openssl" {
+    operation = "GTE"
+    version   = "1.1.1"
+}
+
+Requirement "zps-legacy" {
+    method = "conflicts"
+}
+
+Dir "/usr/bin" {
+    mode = 0750
+}
+
+File "usr/bin/zps" {
+    owner = "zps"
+    group = "wheel"
+    mode  = "0755"
+}
+
+SymLink "usr/bin/zpkg" {
+    target = "zps"
+}
+
+HardLink "usr/bin/zps-hard" {
+    target = "usr/bin/zps"
+}
+"#;
+
+    #[test]
+    fn test_manifest() -> Result<(), ParseError> {
+        let manifest = Zpkgfile::parse(ZPKGFILE)?.manifest(OSArch::new(OS::Darwin, Arch::Arm64))?;
+
+        assert_eq!(manifest.zpkg.name, "zps");
+        assert_eq!(manifest.zpkg.version, "1.0.0");
+        assert_eq!(manifest.zpkg.os, "linux");
+        assert_eq!(manifest.zpkg.arch, "x86_64");
+        assert_eq!(manifest.zpkg.description, manifest.zpkg.summary);
+
+        let requirements: Vec<(&str, &str, &str, &str)> = manifest.requirements.iter()
+            .map(|r| (r.name.as_str(), r.method.as_str(), r.operation.as_str(), r.version.as_str()))
+            .collect();
+        assert_eq!(requirements, vec![("openssl", "depends", "GTE", "1.1.1"), ("zps-legacy", "conflicts", "ANY", "")]);
+
+        assert_eq!(manifest.dirs.len(), 1);
+        assert_eq!(manifest.dirs[0].path, "usr/bin");
+        assert_eq!(manifest.dirs[0].owner, "root");
+        assert_eq!(manifest.dirs[0].mode, 0o750);
+
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].path, "usr/bin/zps");
+        assert_eq!(manifest.files[0].owner, "zps");
+        assert_eq!(manifest.files[0].group, "wheel");
+        assert_eq!(manifest.files[0].mode, 0o755);
+
+        assert_eq!(manifest.symlinks.len(), 1);
+        assert_eq!(manifest.symlinks[0].path, "usr/bin/zpkg");
+        assert_eq!(manifest.symlinks[0].target, "zps");
+        assert_eq!(manifest.symlinks[0].owner, "root");
+
+        assert_eq!(manifest.hardlinks.len(), 1);
+        assert_eq!(manifest.hardlinks[0].target, "usr/bin/zps");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_owned() -> Result<(), ParseError> {
+        let manifest = Zpkgfile::parse(ZPKGFILE)?
+            .manifest_owned(OSArch::new(OS::Linux, Arch::X8664), Some("ci"), None)?;
+
+        assert_eq!(manifest.dirs[0].owner, "ci");
+        assert_eq!(manifest.dirs[0].group, "root");
+        assert_eq!(manifest.files[0].owner, "zps");
+        assert_eq!(manifest.files[0].group, "wheel");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_platforms() -> Result<(), ParseError> {
+        let zpf = Zpkgfile::parse(
+            r#"
+version = "2.1.0"
+prefix  = "opt/zps-${version}"
+
+Zpkg "zps" {
+    version   = "${version}"
+    publisher = "zps.io"
+    summary   = "zps for ${os}-${arch}, costs $${price}"
+}
+
+Dir "${prefix}" {}
+
+Platform "linux-any" "darwin-arm64" {
+    File "${prefix}/zps-${arch}" {}
+
+    Platform "any-arm64" {
+        File "${prefix}/neon" {}
+    }
+}
+"#,
+        )?;
+
+        let linux = zpf.manifest(OSArch::new(OS::Linux, Arch::X8664))?;
+        assert_eq!(linux.zpkg.version, "2.1.0");
+        assert_eq!(linux.zpkg.os, "linux");
+        assert_eq!(linux.zpkg.arch, "x86_64");
+        assert_eq!(linux.zpkg.summary, "zps for linux-x86_64, costs ${price}");
+        assert_eq!(linux.dirs[0].path, "opt/zps-2.1.0");
+        let paths: Vec<&str> = linux.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["opt/zps-2.1.0/zps-x86_64"]);
+
+        let darwin = zpf.manifest(OSArch::new(OS::Darwin, Arch::Arm64))?;
+        let paths: Vec<&str> = darwin.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["opt/zps-2.1.0/zps-arm64", "opt/zps-2.1.0/neon"]);
+
+        let darwin_intel = zpf.manifest(OSArch::new(OS::Darwin, Arch::X8664))?;
+        assert!(darwin_intel.files.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_errors() {
+        let errors = vec![
+            ("Dir \"etc\" {}\n", "1:1: missing Zpkg block"),
+            ("Zpkg \"zps\" {\n  version = \"1.0.0\"\n}\n", "1:1: Zpkg block is missing required attribute 'publisher'"),
+            ("Dir \"etc\" {\n  mode = 0799\n}\n", "2:3: invalid mode '0799'"),
+            ("Dir \"etc\" {\n  color = \"red\"\n}\n", "2:3: unknown attribute 'color' in Dir"),
+            ("Dir \"etc\" {}\nFile \"/etc/\" {}\n", "2:1: duplicate action for path 'etc'"),
+            ("Dir \"../etc\" {}\n", "1:1: invalid path '../etc'"),
+            ("Link \"etc\" {}\n", "1:1: unexpected block type 'Link'"),
+            ("SymLink \"etc\" {}\n", "1:1: SymLink block is missing required attribute 'target'"),
+            ("Requirement \"ssl\" {\n  method = \"needs\"\n}\n", "1:1: invalid requirement method: needs"),
+            ("Requirement \"ssl\" {\n  operation = \"GTE\"\n}\n", "1:1: requirement ssl with comparator GTE requires a version"),
+            ("Requirement \"ssl\" {}\nRequirement \"ssl\" {}\n", "2:1: duplicate requirement 'ssl'"),
+            ("os = \"linux\"\n", "1:1: variable 'os' is reserved"),
+            ("Dir \"${prefix}/etc\" {}\n", "1:1: undefined variable 'prefix'"),
+            ("Platform \"linux\" {}\n", "1:1: invalid platform 'linux'"),
+            ("Platform \"any-any\" {\n  Zpkg \"zps\" {}\n}\n", "2:3: unexpected block type 'Zpkg'"),
+        ];
+
+        for (src, expected) in errors {
+            let err = Zpkgfile::parse(src)
+                .and_then(|zpf| zpf.manifest(OSArch::new(OS::Linux, Arch::X8664)))
+                .err()
+                .unwrap();
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::zpf::lexer::{Lexer, ParseError, Pos, Token};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(String),
+    Bool(bool),
+}
+
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Value,
+    pub pos: Pos,
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub kind: String,
+    pub labels: Vec<String>,
+    pub body: Body,
+    pub pos: Pos,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Body {
+    pub attributes: Vec<Attribute>,
+    pub blocks: Vec<Block>,
+}
+
+pub struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    index: usize,
+}
+
+impl Parser {
+    pub fn parse(src: &str) -> Result<Body, ParseError> {
+        let mut parser = Parser {
+            tokens: Lexer::new(src).tokenize()?,
+            index: 0,
+        };
+
+        let body = parser.body()?;
+
+        match parser.next() {
+            (Token::EOF, _) => Ok(body),
+            (token, pos) => Err(ParseError::new(pos, format!("unexpected {}", token))),
+        }
+    }
+
+    fn peek(&self) -> &(Token, Pos) {
+        &self.tokens[self.index]
+    }
+
+    fn next(&mut self) -> (Token, Pos) {
+        let token = self.tokens[self.index].clone();
+
+        // EOF is always last, never advance past it
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+
+        token
+    }
+
+    // Parses attributes and blocks until a closing brace or EOF
+    fn body(&mut self) -> Result<Body, ParseError> {
+        let mut body = Body::default();
+
+        loop {
+            let (name, pos) = match self.peek() {
+                (Token::RBrace, _) | (Token::EOF, _) => return Ok(body),
+                (Token::Ident(name), pos) => (name.clone(), *pos),
+                (token, pos) => {
+                    return Err(ParseError::new(
+                        *pos,
+                        format!("expected attribute or block, found {}", token),
+                    ))
+                }
+            };
+            self.next();
+
+            match self.peek().0 {
+                Token::Assign => {
+                    self.next();
+                    let value = self.value()?;
+
+                    body.attributes.push(Attribute { name, value, pos })
+                }
+                _ => body.blocks.push(self.block(name, pos)?),
+            }
+        }
+    }
+
+    fn block(&mut self, kind: String, pos: Pos) -> Result<Block, ParseError> {
+        let mut labels = Vec::new();
+
+        loop {
+            match self.next() {
+                (Token::String(label), _) => labels.push(label),
+                (Token::LBrace, _) => break,
+                (token, pos) => {
+                    return Err(ParseError::new(
+                        pos,
+                        format!("expected label or '{{' after '{}', found {}", kind, token),
+                    ))
+                }
+            }
+        }
+
+        let body = self.body()?;
+
+        match self.next() {
+            (Token::RBrace, _) => Ok(Block {
+                kind,
+                labels,
+                body,
+                pos,
+            }),
+            (_, end) => Err(ParseError::new(
+                end,
+                format!("unclosed block '{}' opened at {}", kind, pos),
+            )),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.next() {
+            (Token::String(value), _) => Ok(Value::String(value)),
+            (Token::Number(value), _) => Ok(Value::Number(value)),
+            (Token::Ident(ref value), _) if value == "true" => Ok(Value::Bool(true)),
+            (Token::Ident(ref value), _) if value == "false" => Ok(Value::Bool(false)),
+            (token, pos) => Err(ParseError::new(pos, format!("expected value, found {}", token))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<(), ParseError> {
+        let body = Parser::parse(
+            r#"
+            version = "1.0.0"
+
+            Dir "etc/zps" {
+                owner = "root"
+                mode = 0755
+            }
+            "#,
+        )?;
+
+        assert_eq!(body.attributes.len(), 1);
+        assert_eq!(body.attributes[0].name, "version");
+        assert_eq!(body.attributes[0].value, Value::String("1.0.0".to_string()));
+
+        assert_eq!(body.blocks.len(), 1);
+        assert_eq!(body.blocks[0].kind, "Dir");
+        assert_eq!(body.blocks[0].labels, vec!["etc/zps".to_string()]);
+        assert_eq!(body.blocks[0].pos, Pos { line: 4, column: 13 });
+        assert_eq!(body.blocks[0].body.attributes[1].value, Value::Number("0755".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let missing_value = Parser::parse("Dir \"etc\" {\n  mode =\n}\n").unwrap_err();
+        assert_eq!(missing_value.to_string(), "3:1: expected value, found '}'");
+
+        let unclosed = Parser::parse("Dir \"etc\" {\n  mode = 0755\n").unwrap_err();
+        assert_eq!(unclosed.to_string(), "3:1: unclosed block 'Dir' opened at 1:1");
+
+        let stray = Parser::parse("}").unwrap_err();
+        assert_eq!(stray.to_string(), "1:1: unexpected '}'");
+    }
+}
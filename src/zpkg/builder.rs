@@ -1,19 +1,24 @@
+use std::collections::HashSet;
 use std::env;
 use std::fs::metadata;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use anyhow::*;
+use chrono::{DateTime, Utc};
 use event_emitter_rs::EventEmitter;
 use serde::Deserialize;
 use walkdir::WalkDir;
 
 use crate::{Emitter, Package, Phase};
-use crate::action::{Action, Dir, File, Manifest, Zpkg};
+use crate::action::{Action, Manifest};
 use crate::fs::Resolver;
+use crate::platform::{Arch, OSArch, OS};
 use crate::provider::{Options, provider_for};
 use crate::zpkg::header::{CompType, Header, HeaderV1, Version, HashMethod};
 use crate::zpkg::payload;
 use crate::zpkg::writer::Writer;
+use crate::zpf::Zpkgfile;
 use std::borrow::BorrowMut;
 
 const DEFAULT_ZPF_PATH: &str = "Zpkgfile";
@@ -33,10 +38,17 @@ pub struct Builder {
     owner: Option<String>,
     group: Option<String>,
 
+    os: Option<String>,
+    arch: Option<String>,
+    all_platforms: bool,
+
     secure: bool,
     restrict: bool,
 
+    built: DateTime<Utc>,
+    zpf: Option<Zpkgfile>,
     file_path: Option<PathBuf>,
+    file_paths: Vec<PathBuf>,
     manifest: Option<Manifest>,
 }
 
@@ -52,9 +64,15 @@ impl Builder {
             zpf_path: None,
             owner: None,
             group: None,
+            os: None,
+            arch: None,
+            all_platforms: false,
             secure: true,
             restrict: false,
+            built: Utc::now(),
+            zpf: None,
             file_path: None,
+            file_paths: Vec::new(),
             manifest: None,
         }
     }
@@ -94,6 +112,22 @@ impl Builder {
         self
     }
 
+    pub fn os(&mut self, os: String) -> &mut Builder {
+        self.os = Some(os);
+        self
+    }
+
+    pub fn arch(&mut self, arch: String) -> &mut Builder {
+        self.arch = Some(arch);
+        self
+    }
+
+    // Build one package per supported platform, narrowed by os and arch if set
+    pub fn all_platforms(&mut self) -> &mut Builder {
+        self.all_platforms = true;
+        self
+    }
+
     pub fn debug(&mut self) -> &mut Builder {
         self.provider_options.debug = true;
         self
@@ -119,14 +153,41 @@ impl Builder {
         self
     }
 
-    pub fn build(&mut self) -> Result<Manifest, Error> {
+    pub fn build(&mut self) -> Result<Vec<Manifest>, Error> {
         self.set_paths()?;
 
+        self.built = Utc::now();
+        self.zpf = Some(Zpkgfile::load(self.zpf_path.as_ref().unwrap())?);
+
+        let mut manifests: Vec<Manifest> = Vec::new();
+        let mut file_paths: HashSet<PathBuf> = HashSet::new();
+        self.file_paths = Vec::new();
+
+        for target in self.targets()? {
+            self.load_zpf(target)?;
+
+            // Packages pinned to a platform in the Zpkgfile are only built once
+            self.validate()?;
+            if !file_paths.insert(self.file_path.clone().unwrap()) {
+                continue;
+            }
+
+            manifests.push(self.package()?);
+            self.file_paths.push(self.file_path.clone().unwrap());
+        }
+
+        Ok(manifests)
+    }
+
+    // Paths of the packages written by the last build
+    pub fn file_paths(&self) -> &[PathBuf] {
+        self.file_paths.as_slice()
+    }
+
+    fn package(&mut self) -> Result<Manifest, Error> {
         let writer = Writer::new();
         let mut payload = payload::Writer::new(self.compression, self.hash_method, self.provider_options.work_path.as_ref().unwrap().as_path())?;
 
-        self.load_zpf()?;
-
         // This will restrict file system actions to those defined in the Zpkgfile
         if !self.restrict {
             self.resolve()?;
@@ -152,6 +213,29 @@ impl Builder {
         Ok(self.manifest.clone().unwrap())
     }
 
+    fn targets(&self) -> Result<Vec<OSArch>, Error> {
+        let os = match self.os.as_ref() {
+            Some(os) => Some(OS::from_str(os).map_err(|_| anyhow!("unsupported os: {}", os))?),
+            None => None,
+        };
+
+        let arch = match self.arch.as_ref() {
+            Some(arch) => Some(Arch::from_str(arch).map_err(|_| anyhow!("unsupported arch: {}", arch))?),
+            None => None,
+        };
+
+        if self.all_platforms {
+            return Ok(OSArch::platforms()
+                .into_iter()
+                .filter(|p| os.map_or(true, |os| p.os() == os) && arch.map_or(true, |arch| p.arch() == arch))
+                .collect());
+        }
+
+        let current = OSArch::from_current();
+
+        Ok(vec![OSArch::new(os.unwrap_or(current.os()), arch.unwrap_or(current.arch()))])
+    }
+
     fn set_paths(&mut self) -> Result<(), Error> {
         if self.output_path.is_none() {
             self.output_path = Some(env::current_dir()?);
@@ -179,58 +263,25 @@ impl Builder {
         Ok(())
     }
 
-    fn load_zpf(&mut self) -> Result<(), Error> {
-        self.manifest = {
-            let mut manifest = Manifest::new(Zpkg {
-                name: "test".to_string(),
-                version: "1.0.0".to_string(),
-                publisher: "thing".to_string(),
-                arch: "x86_64".to_string(),
-                os: "darwin".to_string(),
-                summary: "banan".to_string(),
-                description: "narf".to_string(),
-            });
-
-            manifest.dirs = vec![
-                Dir {
-                    path: "nacho/taco".to_string(),
-                    owner: "root".to_string(),
-                    group: "root".to_string(),
-                    mode: 0o0750,
-                }
-            ];
-
-            manifest.files = vec![
-                File {
-                    path: "nacho/taco/sniffer".to_string(),
-                    owner: "root".to_string(),
-                    group: "root".to_string(),
-                    mode: 0o0640,
-                    digest: "".to_string(),
-                    offset: 0,
-                    csize: 0,
-                    size: 0
-                },
-                File {
-                    path: "nacho/oof.txt".to_string(),
-                    owner: "root".to_string(),
-                    group: "root".to_string(),
-                    mode: 0o0640,
-                    digest: "".to_string(),
-                    offset: 0,
-                    csize: 0,
-                    size: 0
-                }
-            ];
-
-            Some(manifest)
-        };
+    fn load_zpf(&mut self, target: OSArch) -> Result<(), Error> {
+        // Explicit ownership in the Zpkgfile takes precedence over the builder overrides
+        let mut manifest = self.zpf.as_ref().unwrap().manifest_owned(target, self.owner.as_deref(), self.group.as_deref())
+            .map_err(|err| anyhow!("{}:{}", self.zpf_path.as_ref().unwrap().display(), err))?;
+
+        // Versions without a timestamp share the build time across all targets
+        let mut version = crate::Version::from(manifest.zpkg.version.as_str())?;
+        if !manifest.zpkg.version.contains(':') {
+            version.time = Some(self.built);
+        }
+        manifest.zpkg.version = version.to_string();
+
+        self.manifest = Some(manifest);
 
         Ok(())
     }
 
     fn resolve(&mut self) -> Result<(), Error> {
-        for action in Resolver::walk(self.provider_options.target_path.as_ref().unwrap(), self.secure)?.into_iter() {
+        for action in Resolver::walk(self.provider_options.target_path.as_ref().unwrap(), self.secure, self.owner.as_deref(), self.group.as_deref())?.into_iter() {
             self.manifest.as_mut().unwrap().add(action);
         }
         Ok(())
@@ -243,7 +294,9 @@ impl Builder {
         for action in self.manifest.as_ref().unwrap().actions().into_iter() {
             let mut_action= provider_for(action).realize(self.provider_options.clone(), Phase::Package, None, Some(payload))?;
 
-            println!("{}", mut_action.to_string());
+            if self.provider_options.verbose {
+                self.emitter.sync_emit("info", mut_action.to_string());
+            }
 
             actions.push(mut_action);
         }
@@ -276,11 +329,122 @@ impl Emitter for Builder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::zpkg::tests::{build, test_root, zpkgfile};
 
     #[test]
     fn test_builder() -> Result<(), Error>{
-        let manifest = Builder::new().build()?;
-        assert_eq!(manifest.zpkg.name, "test".to_string());
+        let root = test_root("zpstestbuilder")?;
+        std::fs::create_dir_all(root.join("proto/usr/bin"))?;
+        std::fs::write(root.join("proto/usr/bin/zps"), "zps")?;
+        std::fs::write(root.join("Zpkgfile"), zpkgfile("zps", "1.0.0"))?;
+
+        let manifests = Builder::new()
+            .zpf(root.to_str().unwrap().to_string())
+            .output(root.to_str().unwrap().to_string())
+            .work(root.to_str().unwrap().to_string())
+            .build()?;
+        assert_eq!(manifests.len(), 1);
+        assert_eq!(manifests[0].zpkg.name, "zps".to_string());
+        assert_eq!(manifests[0].files[0].path, "usr/bin/zps");
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_platforms() -> Result<(), Error>{
+        let root = test_root("zpstestbuilderplatforms")?;
+        std::fs::write(root.join("Zpkgfile"), zpkgfile("platforms", "1.0.0"))?;
+
+        let mut builder = Builder::new();
+        let manifests = builder
+            .zpf(root.to_str().unwrap().to_string())
+            .output(root.to_str().unwrap().to_string())
+            .work(root.to_str().unwrap().to_string())
+            .os("linux".to_string())
+            .all_platforms()
+            .build()?;
+
+        let platforms: Vec<String> = manifests.iter()
+            .map(|m| format!("{}-{}", m.zpkg.os, m.zpkg.arch))
+            .collect();
+        assert_eq!(platforms, vec!["linux-any", "linux-arm64", "linux-x86_64"]);
+        assert!(builder.file_paths().iter().all(|path| path.starts_with(&root)));
+
+        // All targets of a single build share the same version stamp
+        assert!(manifests.iter().all(|m| m.zpkg.version == manifests[0].zpkg.version));
+
+        std::fs::remove_dir_all(&root)?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_builder_ownership() -> Result<(), Error>{
+        let root = test_root("zpstestbuilderownership")?;
+        std::fs::create_dir_all(root.join("proto/etc/zps"))?;
+        std::fs::write(root.join("proto/etc/zps/config"), "config")?;
+        std::fs::write(root.join("proto/etc/zps/secret"), "secret")?;
+        std::os::unix::fs::symlink("config", root.join("proto/etc/zps/config.default"))?;
+        std::fs::write(root.join("Zpkgfile"), format!("{}{}", zpkgfile("ownership", "1.0.0"), r#"
+File "etc/zps/secret" {
+    owner = "zps"
+    mode  = 0600
+}
+"#))?;
+
+        let manifests = Builder::new()
+            .zpf(root.to_str().unwrap().to_string())
+            .output(root.to_str().unwrap().to_string())
+            .work(root.to_str().unwrap().to_string())
+            .owner("ci".to_string())
+            .group("staff".to_string())
+            .build()?;
+
+        let manifest = &manifests[0];
+        let owners: Vec<(&str, &str, &str)> = manifest.files.iter()
+            .map(|f| (f.path.as_str(), f.owner.as_str(), f.group.as_str()))
+            .collect();
+
+        assert_eq!(owners, vec![("etc/zps/secret", "zps", "staff"), ("etc/zps/config", "ci", "staff")]);
+        assert!(manifest.dirs.iter().all(|d| d.owner == "ci" && d.group == "staff"));
+
+        assert_eq!(manifest.symlinks.len(), 1);
+        assert_eq!(manifest.symlinks[0].target, "config");
+        assert_eq!(manifest.symlinks[0].owner, "ci");
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_requirements() -> Result<(), Error>{
+        let root = test_root("zpstestbuilderrequirements")?;
+        let zpkg = build(&root, &format!("{}{}", zpkgfile("nachos", "1.0.0"), r#"
+Requirement "salsa" {
+    operation = "GTE"
+    version   = "2.0.0"
+}
+
+Requirement "tacos" {
+    method = "conflicts"
+}
+"#), &root)?;
+
+        let mut reader = crate::zpkg::reader::Reader::new(&zpkg, &root);
+        reader.read()?;
+
+        let manifest = reader.manifest.take().unwrap();
+        assert_eq!(manifest.requirements.len(), 2);
+        assert_eq!(manifest.requirements[0].name, "salsa");
+        assert_eq!(manifest.requirements[1].method, "conflicts");
+
+        let package = Package::from(manifest)?;
+        let requirements: Vec<String> = package.requirements.iter()
+            .map(|r| format!("{} {}", r.method, r))
+            .collect();
+        assert_eq!(requirements, vec!["depends salsa>=2.0.0", "conflicts tacos"]);
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
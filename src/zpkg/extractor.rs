@@ -0,0 +1,275 @@
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use event_emitter_rs::EventEmitter;
+use serde::Deserialize;
+
+use crate::Emitter;
+use crate::action::{Action, Manifest};
+use crate::fs;
+use crate::zpkg::reader::Reader;
+
+// Unpacks a zpkg into an arbitrary directory, no package state is touched
+pub struct Extractor {
+    emitter: EventEmitter,
+
+    zpkg_path: Option<PathBuf>,
+    target_path: Option<PathBuf>,
+    work_path: Option<PathBuf>,
+
+    permissions: bool,
+    ownership: bool,
+    verbose: bool,
+}
+
+impl Extractor {
+    pub fn new() -> Extractor {
+        Extractor {
+            emitter: EventEmitter::new(),
+            zpkg_path: None,
+            target_path: None,
+            work_path: None,
+            permissions: false,
+            ownership: false,
+            verbose: false,
+        }
+    }
+
+    pub fn zpkg(&mut self, path: String) -> &mut Extractor {
+        self.zpkg_path = Some(PathBuf::from(path));
+        self
+    }
+
+    pub fn target(&mut self, path: String) -> &mut Extractor {
+        self.target_path = Some(PathBuf::from(path));
+        self
+    }
+
+    pub fn work(&mut self, path: String) -> &mut Extractor {
+        self.work_path = Some(PathBuf::from(path));
+        self
+    }
+
+    // Apply modes from the manifest
+    pub fn permissions(&mut self) -> &mut Extractor {
+        self.permissions = true;
+        self
+    }
+
+    // Apply owner and group from the manifest, only possible as root
+    pub fn ownership(&mut self) -> &mut Extractor {
+        self.ownership = true;
+        self
+    }
+
+    pub fn verbose(&mut self) -> &mut Extractor {
+        self.verbose = true;
+        self
+    }
+
+    pub fn extract(&mut self) -> Result<Manifest, Error> {
+        let zpkg_path = match self.zpkg_path.as_ref() {
+            Some(path) => path.clone(),
+            None => return Err(anyhow!("zpkg path is required")),
+        };
+
+        let target_path = match self.target_path.as_ref() {
+            Some(path) => path.clone(),
+            None => env::current_dir()?,
+        };
+
+        let work_path = match self.work_path.as_ref() {
+            Some(path) => path.clone(),
+            None => env::current_dir()?,
+        };
+
+        let mut reader = Reader::new(&zpkg_path, &work_path);
+        reader.read()?;
+
+        let payload = reader.payload()?;
+        let manifest = reader.manifest.take().unwrap();
+
+        let chown = self.ownership && fs::is_root();
+        if self.ownership && !chown {
+            self.emitter.sync_emit("warn", "not running as root, skipping ownership".to_string());
+        }
+
+        std::fs::create_dir_all(&target_path)?;
+
+        // Parents sort before their children
+        let mut dirs = manifest.dirs.clone();
+        dirs.sort_by(|a, b| a.path.cmp(&b.path));
+
+        for dir in dirs.iter() {
+            let path = fs::tree_path(&target_path, &dir.path)?;
+            std::fs::create_dir_all(&path)?;
+
+            // An earlier extraction may have left it read only, modes are applied again below
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            if mode & 0o700 != 0o700 {
+                fs::chmod(&path, mode | 0o700)?;
+            }
+        }
+
+        for file in manifest.files.iter() {
+            let path = fs::tree_path(&target_path, &file.path)?;
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            Self::clear(&path)?;
+            payload.get_to_path(file, &path)?;
+
+            if self.permissions {
+                fs::chmod(&path, file.mode)?;
+            }
+
+            if chown {
+                fs::chown(&path, &file.owner, &file.group)?;
+            }
+
+            if self.verbose {
+                self.emitter.sync_emit("info", file.to_string());
+            }
+        }
+
+        for symlink in manifest.symlinks.iter() {
+            let path = fs::tree_path(&target_path, &symlink.path)?;
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            Self::clear(&path)?;
+            std::os::unix::fs::symlink(&symlink.target, &path)?;
+
+            if chown {
+                fs::chown(&path, &symlink.owner, &symlink.group)?;
+            }
+
+            if self.verbose {
+                self.emitter.sync_emit("info", symlink.to_string());
+            }
+        }
+
+        for hardlink in manifest.hardlinks.iter() {
+            let path = fs::tree_path(&target_path, &hardlink.path)?;
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            Self::clear(&path)?;
+            std::fs::hard_link(fs::tree_path(&target_path, &hardlink.target)?, &path)?;
+
+            if self.verbose {
+                self.emitter.sync_emit("info", hardlink.to_string());
+            }
+        }
+
+        // Directory modes are applied last, deepest first, so restrictive modes don't block extraction
+        for dir in dirs.iter().rev() {
+            let path = fs::tree_path(&target_path, &dir.path)?;
+
+            if self.permissions {
+                fs::chmod(&path, dir.mode)?;
+            }
+
+            if chown {
+                fs::chown(&path, &dir.owner, &dir.group)?;
+            }
+
+            if self.verbose {
+                self.emitter.sync_emit("info", dir.to_string());
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    // Anything but a directory left by an earlier extraction into the same target is replaced
+    fn clear(path: &Path) -> Result<(), Error> {
+        if std::fs::symlink_metadata(path).map(|m| !m.is_dir()).unwrap_or(false) {
+            std::fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Emitter for Extractor {
+    fn on<F, T>(&mut self, event: &str, callback: F) -> String
+        where
+                for<'de> T: Deserialize<'de>,
+                F: Fn(T) + 'static + Sync + Send
+    {
+        let id = self.emitter.on_limited(event, None, callback);
+        return id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zpkg::tests::{build, test_root, zpkgfile};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    #[test]
+    fn test_extract() -> Result<(), Error> {
+        let root = test_root("zpstestextract")?;
+        std::fs::create_dir_all(root.join("proto/usr/bin"))?;
+        std::fs::write(root.join("proto/usr/bin/zps"), "#!/bin/sh\necho zps\n")?;
+        std::fs::hard_link(root.join("proto/usr/bin/zps"), root.join("proto/usr/bin/zps-hard"))?;
+
+        let zpkg = build(&root, &format!("{}{}", zpkgfile("extract", "1.0.0"), r#"
+Dir "usr/bin" {
+    mode = 0555
+}
+
+File "usr/bin/zps" {
+    mode = 0750
+}
+
+File "etc/empty" {
+    mode = 0444
+}
+
+SymLink "usr/bin/zpkg" {
+    target = "zps"
+}
+"#), &root)?;
+
+        // Extracting again over a previous extraction replaces what's there, read only modes included
+        let out = root.join("out");
+        Extractor::new()
+            .zpkg(zpkg.to_str().unwrap().to_string())
+            .target(out.to_str().unwrap().to_string())
+            .work(root.to_str().unwrap().to_string())
+            .permissions()
+            .extract()?;
+
+        let manifest = Extractor::new()
+            .zpkg(zpkg.to_str().unwrap().to_string())
+            .target(out.to_str().unwrap().to_string())
+            .work(root.to_str().unwrap().to_string())
+            .permissions()
+            .extract()?;
+
+        assert_eq!(manifest.zpkg.name, "extract");
+        assert_eq!(std::fs::read(out.join("usr/bin/zps"))?, std::fs::read(root.join("proto/usr/bin/zps"))?);
+        assert_eq!(std::fs::metadata(out.join("usr/bin/zps"))?.permissions().mode() & 0o7777, 0o750);
+        assert_eq!(std::fs::metadata(out.join("usr/bin"))?.permissions().mode() & 0o7777, 0o555);
+        assert_eq!(std::fs::metadata(out.join("etc/empty"))?.len(), 0);
+        assert_eq!(std::fs::metadata(out.join("etc/empty"))?.permissions().mode() & 0o7777, 0o444);
+        assert_eq!(std::fs::read_link(out.join("usr/bin/zpkg"))?.to_str().unwrap(), "zps");
+        assert_eq!(manifest.hardlinks.len(), 1);
+        assert_eq!(std::fs::metadata(out.join("usr/bin/zps-hard"))?.ino(), std::fs::metadata(out.join("usr/bin/zps"))?.ino());
+
+        std::fs::set_permissions(out.join("usr/bin"), std::fs::Permissions::from_mode(0o755))?;
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
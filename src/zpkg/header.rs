@@ -1,6 +1,12 @@
-use std::fs::File;
-use anyhow::Error;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use std::io::Read;
+use std::str::FromStr;
+use anyhow::{anyhow, Error};
 use bytes::BufMut;
+use byteorder::{ReadBytesExt, LittleEndian};
+use crate::zpkg::reader::ReadError;
 
 pub(crate) const MAGIC: &[u8; 5] = b"zpkg!";
 
@@ -14,20 +20,51 @@ pub enum HashMethod {
     SHA3_256 = 0
 }
 
-impl From<u8> for CompType {
-    fn from(val: u8) -> CompType {
+impl Display for CompType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompType::ZSTD => write!(f, "zstd")
+        }
+    }
+}
+
+impl Display for HashMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashMethod::SHA3_256 => write!(f, "sha3-256")
+        }
+    }
+}
+
+impl FromStr for CompType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CompType, Error> {
+        match s {
+            "zstd" => Ok(CompType::ZSTD),
+            _ => Err(anyhow!("unsupported compression type: {}", s))
+        }
+    }
+}
+
+impl TryFrom<u8> for CompType {
+    type Error = ReadError;
+
+    fn try_from(val: u8) -> Result<CompType, ReadError> {
         match val {
-            0 => CompType::ZSTD,
-            _ => CompType::ZSTD
+            0 => Ok(CompType::ZSTD),
+            _ => Err(ReadError::UnknownCompression(val))
         }
     }
 }
 
-impl From<u8> for HashMethod {
-    fn from(val: u8) -> HashMethod {
+impl TryFrom<u8> for HashMethod {
+    type Error = ReadError;
+
+    fn try_from(val: u8) -> Result<HashMethod, ReadError> {
         match val {
-            0 => HashMethod::SHA3_256,
-            _ => HashMethod::SHA3_256
+            0 => Ok(HashMethod::SHA3_256),
+            _ => Err(ReadError::UnknownHashMethod(val))
         }
     }
 }
@@ -37,6 +74,17 @@ pub enum Version {
     V1 = 1
 }
 
+impl TryFrom<u8> for Version {
+    type Error = ReadError;
+
+    fn try_from(val: u8) -> Result<Version, ReadError> {
+        match val {
+            1 => Ok(Version::V1),
+            _ => Err(ReadError::UnknownVersion(val))
+        }
+    }
+}
+
 pub trait Header {
     fn comp_type(&self) -> u8;
     fn hash_method(&self) -> u8;
@@ -63,6 +111,15 @@ impl HeaderV1 {
         header
     }
 
+    // Reads the remainder of a V1 header, the version byte has already been consumed
+    pub fn read<R: Read>(reader: &mut R) -> Result<HeaderV1, ReadError> {
+        let compression = CompType::try_from(reader.read_u8()?)?;
+        let hash_method = HashMethod::try_from(reader.read_u8()?)?;
+        let manifest_len = reader.read_u32::<LittleEndian>()?;
+
+        Ok(Self::new(compression, hash_method, manifest_len))
+    }
+
     pub fn default() -> HeaderV1 {
         HeaderV1 {
             version: Version::V1 as u8,
@@ -1,7 +1,62 @@
 mod builder;
+mod extractor;
 mod header;
-mod reader;
+pub mod reader;
+mod verifier;
 mod writer;
 pub(crate) mod payload;
 
 pub use builder::*;
+pub use extractor::*;
+pub use verifier::*;
+pub use header::{CompType, HashMethod, Header};
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    use anyhow::Error;
+
+    use crate::zpkg::Builder;
+
+    // A fresh directory under the system temp dir with an empty proto tree
+    pub(crate) fn test_root(name: &str) -> Result<PathBuf, Error> {
+        let root = env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("proto"))?;
+
+        Ok(root)
+    }
+
+    // Just the Zpkg block, other blocks may be appended
+    pub(crate) fn zpkgfile(name: &str, version: &str) -> String {
+        format!(r#"
+Zpkg "{}" {{
+    version   = "{}"
+    publisher = "zps.io"
+    summary   = "{} test"
+}}
+"#, name, version, name)
+    }
+
+    // Builds zpkgfile with root/proto as the proto tree into output, returning the zpkg path
+    pub(crate) fn build(root: &Path, zpkgfile: &str, output: &Path) -> Result<PathBuf, Error> {
+        std::fs::create_dir_all(root.join("proto"))?;
+        std::fs::create_dir_all(output)?;
+        std::fs::write(root.join("Zpkgfile"), zpkgfile)?;
+
+        let mut builder = Builder::new();
+        builder
+            .zpf(root.to_str().unwrap().to_string())
+            .output(output.to_str().unwrap().to_string())
+            .work(root.to_str().unwrap().to_string())
+            .build()?;
+
+        Ok(builder.file_paths()[0].clone())
+    }
+
+    pub(crate) fn build_zpkg(root: &Path, name: &str, version: &str, output: &Path) -> Result<PathBuf, Error> {
+        build(root, &zpkgfile(name, version), output)
+    }
+}
@@ -1,14 +1,89 @@
 use std::fs::File;
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use std::path::{Path, PathBuf};
-use std::io::{BufReader, BufWriter, Write, Seek, SeekFrom};
+use std::io::{BufReader, Read, Write, Seek, SeekFrom};
 use std::io;
+use crate::action;
 use crate::zpkg::header::{CompType, HashMethod};
 use sha3::{Sha3_256, Digest};
-use crate::io::MultiWriter;
-use std::borrow::{BorrowMut, Borrow};
+use crate::io::{MultiWriter, TeeReader};
+use std::borrow::BorrowMut;
 
-pub struct Reader {}
+pub struct Reader {
+    comp_type: CompType,
+    hash_method: HashMethod,
+    path: PathBuf,
+    offset: u64
+}
+
+impl Reader {
+    pub fn new(comp_type: CompType, hash_method: HashMethod, path: &Path, offset: u64) -> Self {
+        Self {
+            comp_type,
+            hash_method,
+            path: PathBuf::from(path),
+            offset
+        }
+    }
+
+    // Streams the payload slice for a file action into dst, verifying size and digest
+    pub fn get(&self, file: &action::File, dst: &mut dyn Write) -> Result<(), Error> {
+        // Zero byte files have no payload
+        if file.csize == 0 {
+            if file.size != 0 {
+                return Err(anyhow!("missing payload for {}: expected {} bytes", file.path, file.size));
+            }
+
+            return Ok(());
+        }
+
+        let offset = self.offset.checked_add(file.offset)
+            .ok_or_else(|| anyhow!("payload offset for {} is out of range", file.path))?;
+
+        let mut src = File::open(self.path.as_path())?;
+        src.seek(SeekFrom::Start(offset))?;
+
+        let slice = src.take(file.csize);
+
+        let mut hasher = match self.hash_method {
+            _ => Sha3_256::new()
+        };
+
+        let size = {
+            let mut dst = MultiWriter::new(vec![Box::new(dst), Box::new(hasher.borrow_mut())]);
+
+            match self.comp_type {
+                CompType::ZSTD => io::copy(&mut zstd::stream::read::Decoder::new(slice)?, &mut dst)?
+            }
+        };
+
+        if size != file.size {
+            return Err(anyhow!("size mismatch for {}: expected {}, got {}", file.path, file.size, size));
+        }
+
+        let digest = format!("{:x}", hasher.finalize());
+
+        if digest != file.digest {
+            return Err(anyhow!("digest mismatch for {}: expected {}, got {}", file.path, file.digest, digest));
+        }
+
+        Ok(())
+    }
+
+    // Writes the file to path, the partial file is removed if verification fails
+    pub fn get_to_path(&self, file: &action::File, path: &Path) -> Result<(), Error> {
+        let mut dst = File::create(path)?;
+
+        match self.get(file, &mut dst).and_then(|_| dst.sync_all().map_err(Error::from)) {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                drop(dst);
+                let _ = std::fs::remove_file(path);
+                Err(err)
+            }
+        }
+    }
+}
 
 pub struct Writer {
     comp_type: CompType,
@@ -58,8 +133,9 @@ impl Writer {
             _ => Sha3_256::new()
         };
 
-        let mut src = BufReader::new(input);
-        let mut dst = MultiWriter::new(vec![Box::new(self.file.borrow_mut()), Box::new(hasher.borrow_mut())]);
+        // The digest covers the uncompressed contents so installed files can be checked against it
+        let src = TeeReader::new(BufReader::new(input), hasher.borrow_mut());
+        let dst = self.file.borrow_mut();
 
         match self.comp_type {
             CompType::ZSTD => zstd::stream::copy_encode(src, dst, 3)?
@@ -69,4 +145,65 @@ impl Writer {
 
         Ok((offset, csize, size, format!{"{:x}",  hasher.finalize()}))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn file_action(path: &str, result: (u64, u64, u64, String)) -> action::File {
+        action::File {
+            path: path.to_string(),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mode: 0o644,
+            offset: result.0,
+            csize: result.1,
+            size: result.2,
+            digest: result.3
+        }
+    }
+
+    #[test]
+    fn test_payload() -> Result<(), Error> {
+        let src_a = env::temp_dir().join("zpstestpayload-a");
+        let src_b = env::temp_dir().join("zpstestpayload-b");
+        std::fs::write(&src_a, "nachos are delicious\n".repeat(100))?;
+        std::fs::write(&src_b, b"tacos")?;
+
+        let mut writer = Writer::new(CompType::ZSTD, HashMethod::SHA3_256, &env::temp_dir())?;
+        let a = file_action("a", writer.put(&src_a)?);
+        let b = file_action("b", writer.put(&src_b)?);
+        let empty = file_action("empty", writer.put(&env::temp_dir().join("zpstestpayload-none"))?);
+
+        assert_eq!(b.offset, a.offset + a.csize);
+        assert_eq!(a.digest, format!("{:x}", Sha3_256::digest(&std::fs::read(&src_a)?)));
+
+        let reader = Reader::new(CompType::ZSTD, HashMethod::SHA3_256, writer.file_path(), 0);
+
+        let mut out: Vec<u8> = Vec::new();
+        reader.get(&b, &mut out)?;
+        assert_eq!(out, b"tacos");
+
+        let dst = env::temp_dir().join("zpstestpayload-out");
+        reader.get_to_path(&a, &dst)?;
+        assert_eq!(std::fs::read(&dst)?, std::fs::read(&src_a)?);
+
+        let mut out: Vec<u8> = Vec::new();
+        reader.get(&empty, &mut out)?;
+        assert!(out.is_empty());
+
+        let mut bad_digest = b.clone();
+        bad_digest.digest = a.digest.clone();
+        assert!(reader.get(&bad_digest, &mut io::sink()).unwrap_err().to_string().starts_with("digest mismatch for b"));
+
+        let mut bad_size = a.clone();
+        bad_size.size += 1;
+        assert!(reader.get_to_path(&bad_size, &dst).unwrap_err().to_string().starts_with("size mismatch for a"));
+        assert!(!dst.exists());
+
+        std::fs::remove_file(writer.file_path())?;
+        Ok(())
+    }
+}
@@ -1,17 +1,60 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use crate::zpkg::header::{Header, Version, HeaderV1, CompType, HashMethod};
+
+use anyhow::{anyhow, Error};
+use byteorder::ReadBytesExt;
+
 use crate::action::Manifest;
-use anyhow::Error;
-use std::fs::File;
-use std::io::BufReader;
-use byteorder::{ReadBytesExt, LittleEndian};
-use url::quirks::hash;
-use std::hash::Hash;
+use crate::zpkg::header::{CompType, HashMethod, Header, HeaderV1, Version, MAGIC};
+use crate::zpkg::payload;
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Truncated,
+    InvalidMagic,
+    UnknownVersion(u8),
+    UnknownCompression(u8),
+    UnknownHashMethod(u8),
+    InvalidManifest(String),
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::Truncated => write!(f, "zpkg is truncated"),
+            ReadError::InvalidMagic => write!(f, "not a zpkg, invalid magic"),
+            ReadError::UnknownVersion(val) => write!(f, "unknown zpkg version: {}", val),
+            ReadError::UnknownCompression(val) => write!(f, "unknown zpkg compression type: {}", val),
+            ReadError::UnknownHashMethod(val) => write!(f, "unknown zpkg hash method: {}", val),
+            ReadError::InvalidManifest(msg) => write!(f, "invalid zpkg manifest: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::Truncated,
+            _ => ReadError::Io(err),
+        }
+    }
+}
 
 pub struct Reader {
     path: PathBuf,
     work_path: PathBuf,
 
+    payload_offset: u64,
+
     pub header: Option<Box<dyn Header>>,
     pub manifest: Option<Manifest>
 }
@@ -21,23 +64,149 @@ impl Reader {
         Self {
             path: PathBuf::from(path),
             work_path: PathBuf::from(work_path),
+            payload_offset: 0,
             header: None,
             manifest: None
         }
     }
 
-    pub fn read (&mut self) -> Result<(), Error> {
+    pub fn read(&mut self) -> Result<(), Error> {
         let file = File::open(self.path.as_path())?;
         let mut reader = BufReader::new(file);
 
-        let _ = reader.read_u8()?;
-        let comp_type = reader.read_u8()?.into();
-        let hash_method = reader.read_u8()?.into();
-        let manifest_len = reader.read_u32::<LittleEndian>()?;
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic).map_err(ReadError::from)?;
+
+        if &magic != MAGIC {
+            return Err(ReadError::InvalidMagic.into());
+        }
+
+        let header: Box<dyn Header> = match Version::try_from(reader.read_u8().map_err(ReadError::from)?)? {
+            Version::V1 => Box::new(HeaderV1::read(&mut reader)?)
+        };
+
+        let mut manifest_bytes = vec![0u8; header.manifest_len() as usize];
+        reader.read_exact(&mut manifest_bytes).map_err(ReadError::from)?;
+
+        let manifest_json = match CompType::try_from(header.comp_type())? {
+            CompType::ZSTD => zstd::stream::decode_all(manifest_bytes.as_slice())
+                .map_err(|err| ReadError::InvalidManifest(err.to_string()))?
+        };
+
+        let manifest: Manifest = serde_json::from_slice(&manifest_json)
+            .map_err(|err| ReadError::InvalidManifest(err.to_string()))?;
+
+        self.payload_offset = (MAGIC.len() + header.to_vec().len() + manifest_bytes.len()) as u64;
+        self.header = Some(header);
+        self.manifest = Some(manifest);
+
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn work_path(&self) -> &Path {
+        self.work_path.as_path()
+    }
+
+    // Offset of the first payload byte, valid after a successful read
+    pub fn payload_offset(&self) -> u64 {
+        self.payload_offset
+    }
+
+    pub fn payload(&self) -> Result<payload::Reader, Error> {
+        let header = match self.header.as_ref() {
+            Some(header) => header,
+            None => return Err(anyhow!("zpkg has not been read: {}", self.path.display()))
+        };
+
+        Ok(payload::Reader::new(
+            CompType::try_from(header.comp_type())?,
+            HashMethod::try_from(header.hash_method())?,
+            self.path.as_path(),
+            self.payload_offset
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Zpkg;
+    use std::env;
+
+    fn manifest() -> Manifest {
+        Manifest::new(Zpkg {
+            name: "test".to_string(),
+            version: "1.0.0:20200320T221640Z".to_string(),
+            publisher: "fezz.io".to_string(),
+            arch: "x86_64".to_string(),
+            os: "linux".to_string(),
+            summary: "Test zpkg".to_string(),
+            description: "Test zpkg, for well testing".to_string()
+        })
+    }
+
+    fn write_zpkg(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("zpstestreader-{}.zpkg", name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
 
-        self.header = Some(Box::new(HeaderV1::new(comp_type, hash_method, manifest_len)));
+    fn zpkg_bytes() -> Vec<u8> {
+        let manifest_bytes = zstd::block::compress(&manifest().to_json().unwrap(), 3).unwrap();
+        let header = HeaderV1::new(CompType::ZSTD, HashMethod::SHA3_256, manifest_bytes.len() as u32);
 
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(header.to_vec());
+        bytes.extend(manifest_bytes);
+        bytes
+    }
+
+    fn read_err(name: &str, bytes: &[u8]) -> ReadError {
+        let mut reader = Reader::new(&write_zpkg(name, bytes), &env::temp_dir());
+        reader.read().unwrap_err().downcast::<ReadError>().unwrap()
+    }
+
+    #[test]
+    fn test_read() -> Result<(), Error> {
+        let bytes = zpkg_bytes();
+        let mut reader = Reader::new(&write_zpkg("valid", &bytes), &env::temp_dir());
+
+        reader.read()?;
+
+        assert_eq!(reader.header.as_ref().unwrap().version(), Version::V1 as u8);
+        assert!(reader.manifest.as_ref().unwrap() == &manifest());
+        assert_eq!(reader.payload_offset(), bytes.len() as u64);
         Ok(())
+    }
 
+    #[test]
+    fn test_read_errors() {
+        let bytes = zpkg_bytes();
+
+        assert!(matches!(read_err("magic", b"zpkg?\x01\x00\x00\x00\x00\x00\x00"), ReadError::InvalidMagic));
+        assert!(matches!(read_err("empty", b""), ReadError::Truncated));
+        assert!(matches!(read_err("header", &bytes[..8]), ReadError::Truncated));
+        assert!(matches!(read_err("manifest", &bytes[..bytes.len() - 1]), ReadError::Truncated));
+
+        let mut version = bytes.clone();
+        version[MAGIC.len()] = 9;
+        assert!(matches!(read_err("version", &version), ReadError::UnknownVersion(9)));
+
+        let mut compression = bytes.clone();
+        compression[MAGIC.len() + 1] = 7;
+        assert!(matches!(read_err("compression", &compression), ReadError::UnknownCompression(7)));
+
+        let mut hash_method = bytes.clone();
+        hash_method[MAGIC.len() + 2] = 3;
+        assert!(matches!(read_err("hash", &hash_method), ReadError::UnknownHashMethod(3)));
+
+        let mut garbled = bytes.clone();
+        let last = garbled.len() - 4;
+        garbled[last] ^= 0xff;
+        assert!(matches!(read_err("garbled", &garbled), ReadError::InvalidManifest(_)));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,233 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+
+use crate::action::File;
+use crate::zpkg::reader::Reader;
+
+pub struct FileReport {
+    pub path: String,
+    pub error: Option<String>,
+}
+
+pub struct Report {
+    pub files: Vec<FileReport>,
+    pub errors: Vec<String>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.files.iter().all(|f| f.error.is_none())
+    }
+}
+
+// Checks every payload slice against the manifest and the payload layout against the file
+pub struct Verifier {
+    path: PathBuf,
+    work_path: PathBuf,
+}
+
+impl Verifier {
+    pub fn new(path: &Path, work_path: &Path) -> Verifier {
+        Verifier {
+            path: PathBuf::from(path),
+            work_path: PathBuf::from(work_path),
+        }
+    }
+
+    pub fn verify(&self) -> Result<Report, Error> {
+        let mut reader = Reader::new(&self.path, &self.work_path);
+        reader.read()?;
+
+        let payload = reader.payload()?;
+        let manifest = reader.manifest.take().unwrap();
+
+        let mut report = Report {
+            files: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        for file in manifest.files.iter() {
+            report.files.push(FileReport {
+                path: file.path.clone(),
+                error: payload.get(file, &mut io::sink()).err().map(|err| err.to_string()),
+            });
+        }
+
+        let payload_len = std::fs::metadata(&self.path)?.len() - reader.payload_offset();
+        report.errors = Self::layout(&manifest.files, payload_len);
+
+        Ok(report)
+    }
+
+    // Payload regions must be disjoint and account for every byte after the manifest
+    fn layout(files: &[File], payload_len: u64) -> Vec<String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        let mut regions: Vec<&File> = files.iter().filter(|f| f.csize > 0).collect();
+        regions.sort_by_key(|f| f.offset);
+
+        // Where the next region starts if nothing is missing or shared
+        let mut end: u64 = 0;
+        let mut last: Option<&File> = None;
+
+        for file in regions {
+            let file_end = match file.offset.checked_add(file.csize) {
+                Some(file_end) => file_end,
+                None => {
+                    errors.push(format!("payload for {} has an offset and size out of range", file.path));
+                    continue;
+                }
+            };
+
+            match last {
+                Some(prev) if file.offset < end => {
+                    errors.push(format!("payload for {} overlaps payload for {}", file.path, prev.path));
+                },
+                _ if file.offset.min(payload_len) > end => {
+                    errors.push(format!("{} bytes of unreferenced data before payload for {}", file.offset.min(payload_len) - end, file.path));
+                },
+                _ => (),
+            }
+
+            if file_end > payload_len {
+                errors.push(format!("payload for {} extends beyond end of file", file.path));
+            }
+
+            if file_end > end {
+                end = file_end;
+                last = Some(file);
+            }
+        }
+
+        if end < payload_len {
+            errors.push(format!("{} bytes of trailing data after payload", payload_len - end));
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Manifest;
+    use crate::zpkg::header::{CompType, HashMethod, Header, HeaderV1};
+    use crate::zpkg::tests::{build, test_root, zpkgfile};
+    use crate::zpkg::writer::Writer;
+    use std::env;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    // Builds a zpkg with payloads for a and b, then rewrites it with mutate applied to the manifest
+    fn write_zpkg<F: Fn(&mut Manifest)>(name: &str, mutate: F) -> Result<PathBuf, Error> {
+        let root = test_root(&format!("zpstestverify-{}", name))?;
+        std::fs::write(root.join("proto/a"), "nachos\n".repeat(64))?;
+        std::fs::write(root.join("proto/b"), "tacos\n".repeat(64))?;
+
+        let built = build(&root, &format!("{}{}", zpkgfile("verify", "1.0.0"), r#"
+File "a" {}
+
+File "b" {}
+"#), &root)?;
+
+        let mut reader = Reader::new(&built, &root);
+        reader.read()?;
+
+        let mut manifest = reader.manifest.take().unwrap();
+        mutate(&mut manifest);
+
+        let payload = root.join("payload");
+        std::fs::write(&payload, &std::fs::read(&built)?[reader.payload_offset() as usize..])?;
+
+        let manifest_bytes = zstd::block::compress(&manifest.to_json()?, 3)?;
+        let header_bytes = HeaderV1::new(CompType::ZSTD, HashMethod::SHA3_256, manifest_bytes.len() as u32).to_vec();
+
+        let path = root.join("verify.zpkg");
+        Writer::new().write(path.to_str().unwrap().to_string(), &header_bytes, &manifest_bytes, &payload)?;
+
+        Ok(path)
+    }
+
+    #[test]
+    fn test_verify() -> Result<(), Error> {
+        let path = write_zpkg("valid", |_| ())?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert!(report.is_ok());
+        assert_eq!(report.files.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_digest() -> Result<(), Error> {
+        let path = write_zpkg("digest", |m| m.files[1].digest = m.files[0].digest.clone())?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert!(!report.is_ok());
+        assert!(report.files[0].error.is_none());
+        assert!(report.files[1].error.as_ref().unwrap().starts_with("digest mismatch for b"));
+        assert!(report.errors.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_overlap() -> Result<(), Error> {
+        let path = write_zpkg("overlap", |m| {
+            let mut c = m.files[0].clone();
+            c.path = "c".to_string();
+            m.files.push(c);
+        })?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert!(report.files.iter().all(|f| f.error.is_none()));
+        assert_eq!(report.errors, vec!["payload for c overlaps payload for a".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_gap() -> Result<(), Error> {
+        let path = write_zpkg("gap", |m| { m.files.remove(0); })?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert!(report.files.iter().all(|f| f.error.is_none()));
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].ends_with(" bytes of unreferenced data before payload for b"), "{}", report.errors[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_overflow() -> Result<(), Error> {
+        let path = write_zpkg("overflow", |m| m.files[1].offset = u64::MAX)?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert!(report.files[0].error.is_none());
+        assert!(report.files[1].error.is_some());
+        assert!(report.errors.contains(&"payload for b has an offset and size out of range".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_trailing() -> Result<(), Error> {
+        let path = write_zpkg("trailing", |_| ())?;
+        OpenOptions::new().append(true).open(&path)?.write_all(b"garbage")?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert_eq!(report.errors, vec!["7 bytes of trailing data after payload".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_truncated() -> Result<(), Error> {
+        let path = write_zpkg("truncated", |_| ())?;
+        let len = std::fs::metadata(&path)?.len();
+        OpenOptions::new().write(true).open(&path)?.set_len(len - 4)?;
+        let report = Verifier::new(&path, &env::temp_dir()).verify()?;
+
+        assert!(report.files[0].error.is_none());
+        assert!(report.files[1].error.is_some());
+        assert_eq!(report.errors, vec!["payload for b extends beyond end of file".to_string()]);
+        Ok(())
+    }
+}